@@ -0,0 +1,87 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use glam::{Quat, Vec3A};
+use rgpnp_rs::problem::Problem;
+use rgpnp_rs::synthetic::{Cloud, Generator};
+use rgpnp_rs::{Pose, PoseEstimator, RotationBound, Solver};
+
+const THRESHOLD: f32 = 0.01;
+
+fn problem(cloud: &Cloud, n: usize, noise: f32, outlier_ratio: f32) -> (Pose, Problem) {
+    let mut generator = Generator::new(0);
+    generator.noise = noise;
+    generator.outlier_ratio = outlier_ratio;
+    let truth = generator.random_pose(Vec3A::ZERO, 8.0);
+    let problem = generator.problem(&truth, cloud, n);
+
+    (truth, problem)
+}
+
+fn solver(problem: &Problem, bound: RotationBound) -> Solver {
+    let mut solver = Solver::new(THRESHOLD, THRESHOLD);
+    *solver.rot_bound() = bound;
+    problem.add_to(&mut solver);
+
+    solver
+}
+
+/// Every rotation bound on every scene type
+fn bounds(c: &mut Criterion) {
+    let scenes = Cloud::presets();
+
+    let mut group = c.benchmark_group("bounds");
+    group.sample_size(10);
+    for (scene, cloud) in &scenes {
+        let (truth, problem) = problem(cloud, 50, 1.0, 0.2);
+        let gravity = truth.rotation * Vec3A::NEG_Z;
+        let (axis, _) = Quat::from_mat3a(&truth.rotation).to_axis_angle();
+
+        for (name, bound) in [
+            ("angle-axis", RotationBound::AngleAxis),
+            ("polar", RotationBound::PolarCoordinate),
+            ("gravity", RotationBound::Gravity(gravity)),
+            ("axis", RotationBound::Axis(axis.into())),
+            ("epnp", RotationBound::EPnP),
+            ("dlt", RotationBound::Dlt),
+            ("homography", RotationBound::Homography),
+        ] {
+            let solver = solver(&problem, bound);
+            group.bench_function(BenchmarkId::new(name, scene), |b| {
+                b.iter(|| solver.estimate())
+            });
+        }
+    }
+    group.finish();
+}
+
+/// The full search as the correspondence count and outlier ratio grow
+fn sweeps(c: &mut Criterion) {
+    let cloud = Cloud::Uniform {
+        min: Vec3A::splat(-2.0),
+        max: Vec3A::splat(2.0),
+    };
+
+    let mut group = c.benchmark_group("correspondences");
+    group.sample_size(10);
+    for n in [20, 50, 100, 200] {
+        let (_, problem) = problem(&cloud, n, 1.0, 0.2);
+        let solver = solver(&problem, RotationBound::AngleAxis);
+        group.bench_function(BenchmarkId::from_parameter(n), |b| {
+            b.iter(|| solver.estimate())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("outlier_ratio");
+    group.sample_size(10);
+    for outlier_ratio in [0.0, 0.2, 0.4] {
+        let (_, problem) = problem(&cloud, 50, 1.0, outlier_ratio);
+        let solver = solver(&problem, RotationBound::AngleAxis);
+        group.bench_function(BenchmarkId::from_parameter(outlier_ratio), |b| {
+            b.iter(|| solver.estimate())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bounds, sweeps);
+criterion_main!(benches);
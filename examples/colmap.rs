@@ -0,0 +1,30 @@
+use rgpnp_rs::metrics;
+use rgpnp_rs::reconstruction::read_colmap;
+use rgpnp_rs::{PoseEstimator, Solver};
+
+/// Solves every image of a COLMAP model and compares with COLMAP's poses,
+/// e.g. `cargo run --release --example colmap -- path/to/sparse/0`
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::args()
+        .nth(1)
+        .ok_or("usage: colmap <model directory>")?;
+
+    for image in read_colmap(dir)? {
+        let mut solver = Solver::new(0.02, 0.02);
+        image.problem.add_to(&mut solver);
+        let solution = solver.estimate();
+
+        let Some(truth) = image.problem.ground_truth else {
+            continue;
+        };
+        println!(
+            "{}: {} correspondences, rotation error {:.6} rad, translation error {:.6}",
+            image.name,
+            image.problem.correspondences.len(),
+            metrics::rotation_error(&solution.refined.rotation, &truth.rotation),
+            metrics::translation_error(&solution.refined, &truth)
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use glam::{Mat3A, Quat, Vec3A};
+use rand::Rng;
+use rgpnp_rs::types::WCoord;
+use rgpnp_rs::{CameraK, PoseEstimator, Snapshot, Solver};
+
+fn main() {
+    const N: usize = 50;
+
+    let k = CameraK {
+        fx: 718.856,
+        fy: 718.856,
+        cx: 607.1928,
+        cy: 185.2157,
+    };
+    let rotation = Mat3A::from_quat(Quat::from_rotation_y(0.3));
+    let translation = Vec3A::new(0.1, -0.2, 0.3);
+
+    let mut rng = rand::thread_rng();
+    let mut solver = Solver::new(0.05, 0.05);
+    for _ in 0..N {
+        let camera = Vec3A::new(
+            rng.gen_range(-2.0..=2.0),
+            rng.gen_range(-1.0..=1.0),
+            rng.gen_range(4.0..=8.0),
+        );
+        let world = rotation.transpose() * (camera - translation);
+        let image = k.as_mat3a() * camera;
+        let image = (image / image.z).truncate().round().as_ivec2();
+
+        solver.add_correspondence(image.into(), WCoord::from(world), &k);
+    }
+
+    // record the problem, e.g. when a solve fails
+    let recorded = serde_json::to_string(&solver.snapshot()).unwrap();
+    println!("snapshot: {} bytes", recorded.len());
+
+    // replay it
+    let snapshot: Snapshot = serde_json::from_str(&recorded).unwrap();
+    let replayed = Solver::from_snapshot(snapshot);
+
+    let original = solver.estimate();
+    let again = replayed.estimate();
+    println!("original: {:?}", original.pose);
+    println!("replayed: {:?}", again.pose);
+    println!(
+        "result  : {}",
+        serde_json::to_string(&again.refined).unwrap()
+    );
+}
@@ -0,0 +1,100 @@
+use glam::{Quat, Vec3A};
+use rgpnp_rs::metrics::{self, Confusion};
+use rgpnp_rs::synthetic::{Cloud, Generator};
+use rgpnp_rs::{Pose, PoseEstimator, RotationBound, Solver};
+use std::fs::File;
+use std::io::{self, Write};
+use std::time::Instant;
+
+const BASE_N: usize = 50;
+const BASE_NOISE: f32 = 1.0;
+const BASE_OUTLIER_RATIO: f32 = 0.2;
+/// Angular threshold of the pairs and rays, about 7 px for the generator's camera
+const THRESHOLD: f32 = 0.01;
+
+/// Sweeps noise, outlier ratio, correspondence count and scene type for every rotation bound,
+/// writing one CSV row per solve, e.g. `cargo run --release --features synthetic --example
+/// report -- report.csv 5`
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let mut output: Box<dyn Write> = match args.next() {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let trials: u64 = match args.next() {
+        Some(trials) => trials.parse()?,
+        None => 3,
+    };
+
+    // each sweep varies one parameter about the base configuration
+    let mut configs = vec![];
+    for noise in [0.0, 0.5, 1.0, 2.0, 4.0] {
+        configs.push(("noise", BASE_N, noise, BASE_OUTLIER_RATIO));
+    }
+    for outlier_ratio in [0.0, 0.2, 0.4, 0.6] {
+        configs.push(("outlier_ratio", BASE_N, BASE_NOISE, outlier_ratio));
+    }
+    for n in [20, 50, 100, 200] {
+        configs.push(("n", n, BASE_NOISE, BASE_OUTLIER_RATIO));
+    }
+
+    writeln!(
+        output,
+        "sweep,bound,scene,n,noise,outlier_ratio,seed,elapsed_ms,expanded,max_queue,rotation_error,translation_error,precision,recall"
+    )?;
+    for (sweep, n, noise, outlier_ratio) in configs {
+        for (scene, cloud) in Cloud::presets() {
+            for seed in 0..trials {
+                let mut generator = Generator::new(seed);
+                generator.noise = noise;
+                generator.outlier_ratio = outlier_ratio;
+                let truth = generator.random_pose(Vec3A::ZERO, 8.0);
+                let problem = generator.problem(&truth, &cloud, n);
+
+                for (bound_name, bound) in bounds(&truth) {
+                    let mut solver = Solver::new(THRESHOLD, THRESHOLD);
+                    *solver.rot_bound() = bound;
+                    problem.add_to(&mut solver);
+
+                    let timer = Instant::now();
+                    let solution = solver.estimate();
+                    let elapsed_ms = timer.elapsed().as_secs_f64() * 1e3;
+
+                    let confusion = Confusion::new(
+                        &solution.inliers,
+                        problem.inliers.as_deref().unwrap_or(&[]),
+                    );
+                    writeln!(
+                        output,
+                        "{sweep},{bound_name},{scene},{},{noise},{outlier_ratio},{seed},{elapsed_ms:.3},{},{},{},{},{},{}",
+                        problem.correspondences.len(),
+                        solution.stats.expanded,
+                        solution.stats.max_queue,
+                        metrics::rotation_error(&solution.refined.rotation, &truth.rotation),
+                        metrics::translation_error(&solution.refined, &truth),
+                        confusion.precision(),
+                        confusion.recall(),
+                    )?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Every rotation bound, with the priors of the reduced searches taken from the ground truth
+fn bounds(truth: &Pose) -> [(&'static str, RotationBound); 7] {
+    let gravity = truth.rotation * Vec3A::NEG_Z;
+    let (axis, _) = Quat::from_mat3a(&truth.rotation).to_axis_angle();
+
+    [
+        ("angle-axis", RotationBound::AngleAxis),
+        ("polar", RotationBound::PolarCoordinate),
+        ("gravity", RotationBound::Gravity(gravity)),
+        ("axis", RotationBound::Axis(axis.into())),
+        ("epnp", RotationBound::EPnP),
+        ("dlt", RotationBound::Dlt),
+        ("homography", RotationBound::Homography),
+    ]
+}
@@ -0,0 +1,44 @@
+use rgpnp_rs::metrics::{self, Confusion};
+use rgpnp_rs::problem::Problem;
+use rgpnp_rs::{PoseEstimator, Solver};
+use std::fs::File;
+use std::io::BufReader;
+
+/// Solves a problem file, e.g. `cargo run --example solve_problem -- examples/data/sample.problem`
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or("usage: solve_problem <problem file>")?;
+    let problem = Problem::read(BufReader::new(File::open(path)?))?;
+
+    let mut solver = Solver::new(0.05, 0.05);
+    problem.add_to(&mut solver);
+    let solution = solver.estimate();
+    println!("pose: {:?}", solution.refined);
+    if let Some(rmse) = metrics::reprojection_rmse(
+        &problem.k,
+        &solution.refined,
+        &problem.correspondences,
+        &solution.inliers,
+    ) {
+        println!("inlier reprojection RMSE: {rmse} px");
+    }
+
+    if let Some(truth) = problem.ground_truth {
+        println!(
+            "rotation error: {} rad, translation error: {}",
+            metrics::rotation_error(&solution.refined.rotation, &truth.rotation),
+            metrics::translation_error(&solution.refined, &truth)
+        );
+    }
+    if let Some(inliers) = &problem.inliers {
+        let confusion = Confusion::new(&solution.inliers, inliers);
+        println!(
+            "inlier precision: {}, recall: {}",
+            confusion.precision(),
+            confusion.recall()
+        );
+    }
+
+    Ok(())
+}
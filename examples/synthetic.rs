@@ -0,0 +1,30 @@
+use glam::Vec3A;
+use rgpnp_rs::metrics::{self, Confusion};
+use rgpnp_rs::synthetic::{Cloud, Generator};
+use rgpnp_rs::{PoseEstimator, RansacSolver};
+
+/// Solves seeded synthetic problems of every kind of point cloud
+fn main() {
+    let clouds = Cloud::presets();
+
+    for (name, cloud) in &clouds {
+        let mut generator = Generator::new(7);
+        generator.noise = 1.0;
+        generator.outlier_ratio = 0.3;
+        let truth = generator.random_pose(Vec3A::ZERO, 8.0);
+        let problem = generator.problem(&truth, cloud, 60);
+
+        let mut solver = RansacSolver::new(0.002, 0.002);
+        problem.add_to(&mut solver);
+        let solution = solver.estimate();
+
+        let confusion =
+            Confusion::new(&solution.inliers, problem.inliers.as_deref().unwrap_or(&[]));
+        println!(
+            "{name}: rotation error {} rad, inlier precision {}, recall {}",
+            metrics::rotation_error(&solution.refined.rotation, &truth.rotation),
+            confusion.precision(),
+            confusion.recall()
+        );
+    }
+}
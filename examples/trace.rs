@@ -0,0 +1,27 @@
+use rgpnp_rs::problem::Problem;
+use rgpnp_rs::Solver;
+use std::fs::File;
+use std::io::BufReader;
+
+/// Records the search of a problem file to `trace.jsonl` and renders the explored angle-axis
+/// cells to `trace.svg`, e.g. `cargo run --example trace -- examples/data/sample.problem`
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or("usage: trace <problem file>")?;
+    let problem = Problem::read(BufReader::new(File::open(path)?))?;
+
+    let mut solver = Solver::new(0.05, 0.05);
+    problem.add_to(&mut solver);
+    let (solution, trace) = solver.solve_traced();
+    println!(
+        "{} events, {} branches expanded",
+        trace.events().len(),
+        solution.stats.expanded
+    );
+
+    trace.write_jsonl(File::create("trace.jsonl")?)?;
+    std::fs::write("trace.svg", trace.render_svg([0, 1]))?;
+
+    Ok(())
+}
@@ -0,0 +1,447 @@
+use glam::{Mat3A, Vec2, Vec3A};
+use rgpnp_rs::calibration::Calibration;
+use rgpnp_rs::degeneracy::{Configuration, Degeneracy};
+use rgpnp_rs::metrics;
+use rgpnp_rs::problem::Problem;
+use rgpnp_rs::reconstruction::read_colmap;
+use rgpnp_rs::types::WCoord;
+use rgpnp_rs::{
+    CameraK, Cost, Distortion, Failure, Pose, PoseEstimator, RansacSolver, RotationBound, Solution,
+    Solver, Threshold,
+};
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+const USAGE: &str = "\
+Solves PnP problems and writes the results as JSON
+
+Usage: rgpnp <INPUT> [OPTIONS]
+
+Input (one of):
+  --problem <FILE>           problem file (see `rgpnp_rs::problem`)
+  --colmap <DIR>             COLMAP model, solving every image
+  --csv <FILE>               lines of `u,v,x,y,z`; needs --camera or --calibration
+  --camera <FX,FY,CX,CY>     intrinsics of the CSV points
+  --calibration <FILE>       OpenCV YAML/XML calibration of the CSV points
+
+Options:
+  --mode <MODE>              angle-axis (default), polar, gravity:<X,Y,Z>, axis:<X,Y,Z>,
+                             epnp, dlt, homography or ransac
+  --plane <X,Y,Z>            normal of the plane of the world points, detected if omitted
+  --r-threshold <RAD>        threshold of the rotation constraints [default: 0.05]
+  --t-threshold <RAD>        threshold of the rays [default: 0.05]
+  --pixel-threshold <PX>     threshold in pixels, replacing the angular ones
+  --auto-threshold           fits the thresholds to the noise of the data
+  --cost <COST>              inlier (default), l1 or l2
+  --tolerance <RATIO>        score gap, relative to the best score, at which the search stops
+  --max-iterations <N>       iterations of ransac
+  --seed <N>                 seed of ransac
+  --output <FILE>            writes the JSON to FILE instead of stdout
+  --help                     prints this message";
+
+enum Input {
+    Problem(PathBuf),
+    Colmap(PathBuf),
+    Csv(PathBuf),
+}
+
+enum Mode {
+    Bnb(RotationBound),
+    Ransac,
+}
+
+struct Options {
+    input: Input,
+    calibration: Option<Calibration>,
+    mode: Mode,
+    r_threshold: f32,
+    t_threshold: f32,
+    pixel_threshold: Option<f32>,
+    auto_threshold: bool,
+    cost: Cost,
+    tolerance: Option<f32>,
+    plane: Option<Vec3A>,
+    max_iterations: Option<usize>,
+    seed: Option<u64>,
+    output: Option<PathBuf>,
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(error) = run(&options) {
+        eprintln!("error: {error}");
+        std::process::exit(1);
+    }
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let results: Vec<String> = load(options)?
+        .into_iter()
+        .map(|(name, problem)| solve(options, &name, &problem))
+        .collect();
+    let json = format!("[\n{}\n]\n", results.join(",\n"));
+
+    match &options.output {
+        Some(path) => File::create(path)?.write_all(json.as_bytes())?,
+        None => std::io::stdout().write_all(json.as_bytes())?,
+    }
+
+    Ok(())
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut input = None;
+    let mut camera = None;
+    let mut calibration = None;
+    let mut options = Options {
+        input: Input::Problem(PathBuf::new()),
+        calibration: None,
+        mode: Mode::Bnb(RotationBound::AngleAxis),
+        r_threshold: 0.05,
+        t_threshold: 0.05,
+        pixel_threshold: None,
+        auto_threshold: false,
+        cost: Cost::Inlier,
+        tolerance: None,
+        plane: None,
+        max_iterations: None,
+        seed: None,
+        output: None,
+    };
+
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value of `{flag}`"));
+        match flag.as_str() {
+            "--help" | "-h" => return Ok(None),
+            "--problem" => input = Some(Input::Problem(value()?.into())),
+            "--colmap" => input = Some(Input::Colmap(value()?.into())),
+            "--csv" => input = Some(Input::Csv(value()?.into())),
+            "--camera" => {
+                let [fx, fy, cx, cy] = parse_list(&value()?)?;
+                camera = Some(CameraK { fx, fy, cx, cy });
+            }
+            "--calibration" => {
+                let path = value()?;
+                let text = std::fs::read_to_string(&path).map_err(|e| format!("{path}: {e}"))?;
+                calibration = Some(Calibration::read(&text).map_err(|e| format!("{path}: {e}"))?);
+            }
+            "--mode" => options.mode = parse_mode(&value()?)?,
+            "--r-threshold" => options.r_threshold = parse(&value()?)?,
+            "--t-threshold" => options.t_threshold = parse(&value()?)?,
+            "--pixel-threshold" => options.pixel_threshold = Some(parse(&value()?)?),
+            "--auto-threshold" => options.auto_threshold = true,
+            "--cost" => {
+                options.cost = match value()?.as_str() {
+                    "inlier" => Cost::Inlier,
+                    "l1" => Cost::TruncatedL1,
+                    "l2" => Cost::TruncatedL2,
+                    cost => return Err(format!("unknown cost `{cost}`")),
+                }
+            }
+            "--tolerance" => options.tolerance = Some(parse(&value()?)?),
+            "--plane" => options.plane = Some(parse_list::<3>(&value()?)?.into()),
+            "--max-iterations" => options.max_iterations = Some(parse(&value()?)?),
+            "--seed" => options.seed = Some(parse(&value()?)?),
+            "--output" => options.output = Some(value()?.into()),
+            _ => return Err(format!("unknown argument `{flag}`")),
+        }
+    }
+
+    options.input = input.ok_or("no input given")?;
+    options.calibration = match (camera, calibration) {
+        (Some(k), None) => Some(Calibration {
+            k,
+            distortion: Distortion::None,
+        }),
+        (None, calibration) => calibration,
+        (Some(_), Some(_)) => return Err("both --camera and --calibration given".into()),
+    };
+    if matches!(options.input, Input::Csv(_)) && options.calibration.is_none() {
+        return Err("--csv needs --camera or --calibration".into());
+    }
+
+    Ok(Some(options))
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{value}`"))
+}
+
+fn parse_list<const N: usize>(value: &str) -> Result<[f32; N], String> {
+    let values = value
+        .split(',')
+        .map(|v| parse(v.trim()))
+        .collect::<Result<Vec<f32>, _>>()?;
+
+    values
+        .try_into()
+        .map_err(|_| format!("`{value}` needs {N} comma-separated values"))
+}
+
+fn parse_mode(value: &str) -> Result<Mode, String> {
+    let vector = |v: &str| parse_list::<3>(v).map(Vec3A::from);
+
+    Ok(match value.split_once(':') {
+        None if value == "angle-axis" => Mode::Bnb(RotationBound::AngleAxis),
+        None if value == "polar" => Mode::Bnb(RotationBound::PolarCoordinate),
+        None if value == "epnp" => Mode::Bnb(RotationBound::EPnP),
+        None if value == "dlt" => Mode::Bnb(RotationBound::Dlt),
+        None if value == "homography" => Mode::Bnb(RotationBound::Homography),
+        None if value == "ransac" => Mode::Ransac,
+        Some(("gravity", v)) => Mode::Bnb(RotationBound::Gravity(vector(v)?)),
+        Some(("axis", v)) => Mode::Bnb(RotationBound::Axis(vector(v)?)),
+        _ => return Err(format!("unknown mode `{value}`")),
+    })
+}
+
+/// Reads the named problems of the input
+fn load(options: &Options) -> Result<Vec<(String, Problem)>, Box<dyn Error>> {
+    Ok(match &options.input {
+        Input::Problem(path) => {
+            let problem = Problem::read(BufReader::new(File::open(path)?))?;
+            vec![(path.display().to_string(), problem)]
+        }
+        Input::Colmap(dir) => read_colmap(dir)?
+            .into_iter()
+            .map(|image| (image.name, image.problem))
+            .collect(),
+        Input::Csv(path) => {
+            let Some(Calibration { k, distortion }) = options.calibration else {
+                return Err("--csv needs --camera or --calibration".into());
+            };
+            let mut correspondences = vec![];
+            for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+                let line = line?;
+                let Ok([u, v, x, y, z]) = parse_list::<5>(&line) else {
+                    // a header, or a blank line
+                    if i == 0 || line.trim().is_empty() {
+                        continue;
+                    }
+                    return Err(format!("line {}: expected `u,v,x,y,z`", i + 1).into());
+                };
+                correspondences.push((
+                    distortion.undistort_pixel(&k, Vec2::new(u, v)),
+                    WCoord::from(Vec3A::new(x, y, z)),
+                ));
+            }
+
+            let problem = Problem {
+                k,
+                correspondences,
+                ground_truth: None,
+                inliers: None,
+            };
+            vec![(path.display().to_string(), problem)]
+        }
+    })
+}
+
+fn solve(options: &Options, name: &str, problem: &Problem) -> String {
+    let mut estimator: Box<dyn PoseEstimator> = match options.mode {
+        Mode::Bnb(rot_bound) => {
+            let mut solver = Solver::new(options.r_threshold, options.t_threshold);
+            *solver.rot_bound() = rot_bound;
+            *solver.pixel_threshold() = options.pixel_threshold;
+            *solver.auto_threshold() = options.auto_threshold;
+            *solver.cost() = options.cost;
+            if let Some(tolerance) = options.tolerance {
+                *solver.tolerance() = tolerance;
+            }
+            *solver.plane() = options.plane;
+            Box::new(solver)
+        }
+        Mode::Ransac => {
+            let mut solver = RansacSolver::new(options.r_threshold, options.t_threshold);
+            *solver.pixel_threshold() = options.pixel_threshold;
+            *solver.cost() = options.cost;
+            if let Some(max_iterations) = options.max_iterations {
+                *solver.max_iterations() = max_iterations;
+            }
+            if let Some(seed) = options.seed {
+                *solver.seed() = seed;
+            }
+            Box::new(solver)
+        }
+    };
+    problem.add_to(estimator.as_mut());
+
+    let timer = Instant::now();
+    let solution = estimator.estimate();
+    let elapsed = timer.elapsed();
+
+    to_json(name, problem, &solution, elapsed.as_secs_f64() * 1e3)
+}
+
+fn to_json(name: &str, problem: &Problem, solution: &Solution, elapsed_ms: f64) -> String {
+    let mut json = String::new();
+    let inliers: Vec<&str> = solution
+        .inliers
+        .iter()
+        .map(|&inlier| if inlier { "true" } else { "false" })
+        .collect();
+
+    json.push_str("  {\n");
+    let _ = writeln!(json, "    \"name\": {},", string(name));
+    let _ = writeln!(json, "    \"pose\": {},", pose(&solution.pose));
+    let _ = writeln!(json, "    \"refined\": {},", pose(&solution.refined));
+    let _ = writeln!(json, "    \"score\": {},", number(solution.score));
+    let _ = writeln!(
+        json,
+        "    \"rot_threshold\": {},",
+        threshold(solution.rot_threshold)
+    );
+    let _ = writeln!(
+        json,
+        "    \"ray_threshold\": {},",
+        threshold(solution.ray_threshold)
+    );
+    let _ = writeln!(json, "    \"inliers\": [{}],", inliers.join(", "));
+    let _ = writeln!(
+        json,
+        "    \"degeneracy\": {},",
+        degeneracy(&solution.degeneracy)
+    );
+    let failure = match solution.failure {
+        None => "null".to_string(),
+        Some(Failure::Degenerate) => string("degenerate"),
+        Some(Failure::ClosedForm) => string("closed-form"),
+        Some(Failure::NoPlane) => string("no-plane"),
+    };
+    let _ = writeln!(json, "    \"failure\": {failure},");
+    if let Some(alternative) = &solution.alternative {
+        let inliers: Vec<&str> = alternative
+            .inliers
+            .iter()
+            .map(|&inlier| if inlier { "true" } else { "false" })
+            .collect();
+        let _ = writeln!(
+            json,
+            "    \"alternative\": {{\"pose\": {}, \"score\": {}, \"inliers\": [{}]}},",
+            pose(&alternative.pose),
+            number(alternative.score),
+            inliers.join(", ")
+        );
+    }
+    if let Some(truth) = &problem.ground_truth {
+        let _ = writeln!(
+            json,
+            "    \"error\": {{\"rotation\": {}, \"translation\": {}}},",
+            number(metrics::rotation_error(
+                &solution.refined.rotation,
+                &truth.rotation
+            )),
+            number(metrics::translation_error(&solution.refined, truth))
+        );
+    }
+    let _ = writeln!(
+        json,
+        "    \"stats\": {{\"correspondences\": {}, \"inliers\": {}, \"expanded\": {}, \"max_queue\": {}, \"elapsed_ms\": {elapsed_ms:.3}}}",
+        problem.correspondences.len(),
+        solution.inliers.iter().filter(|&&inlier| inlier).count(),
+        solution.stats.expanded,
+        solution.stats.max_queue
+    );
+    json.push_str("  }");
+
+    json
+}
+
+fn pose(pose: &Pose) -> String {
+    let rows = rows(&pose.rotation).map(|row| format!("[{}]", numbers(&row)));
+
+    format!(
+        "{{\"rotation\": [{}], \"translation\": [{}]}}",
+        rows.join(", "),
+        numbers(&pose.translation.to_array())
+    )
+}
+
+fn rows(m: &Mat3A) -> [[f32; 3]; 3] {
+    let t = m.transpose();
+    [
+        t.x_axis.to_array(),
+        t.y_axis.to_array(),
+        t.z_axis.to_array(),
+    ]
+}
+
+fn degeneracy(degeneracy: &Degeneracy) -> String {
+    let configuration = match degeneracy.configuration {
+        None => "null".to_string(),
+        Some(Configuration::TooFewPoints) => string("too-few-points"),
+        Some(Configuration::Collinear) => string("collinear"),
+        Some(Configuration::FixedLine { .. }) => string("fixed-line"),
+        Some(Configuration::EdgeOn) => string("edge-on"),
+        Some(Configuration::Planar { .. }) => string("planar"),
+        Some(Configuration::PlanarAmbiguity { .. }) => string("planar-ambiguity"),
+    };
+    let indices = |indices: &[usize]| -> String {
+        let indices: Vec<String> = indices.iter().map(usize::to_string).collect();
+        indices.join(", ")
+    };
+
+    format!(
+        "{{\"configuration\": {configuration}, \"pairs\": [{}], \"lines\": [{}]}}",
+        indices(&degeneracy.pairs),
+        indices(&degeneracy.lines)
+    )
+}
+
+fn threshold(threshold: Threshold) -> String {
+    match threshold {
+        Threshold::Angle(v) => format!("{{\"angle\": {}}}", number(v)),
+        Threshold::Pixel(v) => format!("{{\"pixel\": {}}}", number(v)),
+    }
+}
+
+fn numbers(values: &[f32]) -> String {
+    values
+        .iter()
+        .map(|&v| number(v))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// JSON has no NaN nor infinities
+fn number(v: f32) -> String {
+    if v.is_finite() {
+        format!("{v:?}")
+    } else {
+        "null".into()
+    }
+}
+
+fn string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+
+    escaped
+}
@@ -1,11 +1,14 @@
 use crate::bounds::RBound;
 use crate::bounds::RBounds;
-use crate::corres::Corres;
+use crate::corres::{Corres, Threshold};
+use crate::trace::{EventKind, Trace};
+use crate::{Cost, SearchStats};
 use glam::Mat3A;
+use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 
-struct SatisfiedBranch<T> {
-    lower_bound: u32,
+struct SatisfiedBranch<S, T> {
+    lower_bound: S,
     domain: T,
 }
 
@@ -43,12 +46,12 @@ pub fn bnb_rot(initial_rot: impl RBounds, corres: &[Corres], threshold: f32) ->
 }
 
 /// Branch-and-Bound (BB/BnB) method
-pub fn bnb_rot2<R>(initial_rot: Vec<R>, threshold: f32) -> Vec<R>
+pub fn bnb_rot2<R>(initial_rot: Vec<R>, threshold: Threshold, cost: Cost) -> Vec<R>
 where
     R: RBound,
 {
     let mut solution = SatisfiedBranch {
-        lower_bound: 0,
+        lower_bound: 0.0,
         domain: Mat3A::IDENTITY,
     };
     let mut queue = BinaryHeap::from(initial_rot);
@@ -57,7 +60,7 @@ where
     while let Some(bound) = queue.pop() {
         let mut push_pool = vec![];
         for mut divided in bound.subdivide() {
-            divided.compute_bound(threshold);
+            divided.compute_bound(threshold, cost);
             push_pool.push(divided);
         }
         queue.extend(push_pool);
@@ -83,36 +86,78 @@ where
         .collect()
 }
 
+/// Branch in the queue, with its number of subdivisions from the initial branches
+struct Node<R> {
+    bound: R,
+    depth: u32,
+}
+
+impl<R: RBound> PartialEq for Node<R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bound == other.bound
+    }
+}
+impl<R: RBound> Eq for Node<R> {}
+
+impl<R: RBound> PartialOrd for Node<R> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<R: RBound> Ord for Node<R> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.bound.cmp(&other.bound)
+    }
+}
+
 /// Branch-and-Bound (BB/BnB) method
-pub fn bnb_rot3(init: Vec<impl RBound>, threshold: f32) -> Mat3A {
+///
+/// `incumbent` is a known rotation and its score, used as the initial lower bound. The search
+/// stops once no branch can improve the best score by more than `tolerance` times it. The work
+/// done is added to `stats`, and the expanded and pruned branches are recorded to `trace`.
+pub fn bnb_rot3<R: RBound>(
+    init: Vec<R>,
+    threshold: Threshold,
+    cost: Cost,
+    tolerance: f32,
+    incumbent: Option<(f32, Mat3A)>,
+    stats: &mut SearchStats,
+    mut trace: Option<&mut Trace>,
+) -> Mat3A {
+    let (lower_bound, domain) = incumbent.unwrap_or((0.0, Mat3A::IDENTITY));
     let mut solution = SatisfiedBranch {
-        lower_bound: 0,
-        domain: Mat3A::IDENTITY,
+        lower_bound,
+        domain,
     };
 
-    let mut queue = BinaryHeap::from(init);
+    let mut queue: BinaryHeap<_> = init
+        .into_iter()
+        .map(|bound| Node { bound, depth: 0 })
+        .collect();
     queue.reserve(4096);
 
-    while let Some(bound) = queue.pop() {
+    while let Some(Node { bound, depth }) = queue.pop() {
         let mut push_pool = vec![];
-
-        // println!(
-        //     "queue={}, upper={}, lower={}",
-        //     queue.len(),
-        //     bound.upper(),
-        //     bound.lower()
-        // );
+        stats.expanded += 1;
+        if let Some(trace) = trace.as_deref_mut() {
+            let (upper, lower) = (bound.upper(), bound.lower());
+            trace.record(EventKind::Expanded, bound.cell(), depth, upper, lower);
+        }
 
         // Branch op
         for mut divided in bound.subdivide() {
-            divided.compute_bound(threshold);
+            divided.compute_bound(threshold, cost);
 
-            push_pool.push(divided);
+            push_pool.push(Node {
+                bound: divided,
+                depth: depth + 1,
+            });
         }
         queue.extend(push_pool);
+        stats.max_queue = stats.max_queue.max(queue.len());
 
         // update optimal solution
-        for branch in queue.iter() {
+        for Node { bound: branch, .. } in queue.iter() {
             if solution.lower_bound < branch.lower() {
                 solution.lower_bound = branch.lower();
                 solution.domain = branch.rotation();
@@ -120,10 +165,25 @@ pub fn bnb_rot3(init: Vec<impl RBound>, threshold: f32) -> Mat3A {
         }
 
         // Bound op
-        queue.retain(|branch| solution.lower_bound <= branch.upper());
-
-        if queue.peek().map(|bound| bound.upper()) == Some(solution.lower_bound) {
-            return queue.peek().unwrap().rotation();
+        queue.retain(
+            |Node {
+                 bound: branch,
+                 depth,
+             }| {
+                let keep = solution.lower_bound <= branch.upper();
+                if let (false, Some(trace)) = (keep, trace.as_deref_mut()) {
+                    let (upper, lower) = (branch.upper(), branch.lower());
+                    trace.record(EventKind::Pruned, branch.cell(), *depth, upper, lower);
+                }
+
+                keep
+            },
+        );
+
+        if queue.peek().is_some_and(|node| {
+            node.bound.upper() - solution.lower_bound <= tolerance * solution.lower_bound
+        }) {
+            return solution.domain;
         }
     }
 
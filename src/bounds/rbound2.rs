@@ -1,14 +1,19 @@
+use crate::corres::Threshold;
+use crate::trace::Cell;
+use crate::Cost;
 use glam::Mat3A;
 
 pub trait RBound
 where
     Self: Sized + Ord,
 {
-    fn upper(&self) -> u32;
-    fn lower(&self) -> u32;
+    fn upper(&self) -> f32;
+    fn lower(&self) -> f32;
 
     fn subdivide(self) -> Vec<Self>;
 
     fn rotation(&self) -> Mat3A;
-    fn compute_bound(&mut self, threshold: f32);
+    /// Parametrization ranges of the branch
+    fn cell(&self) -> Cell;
+    fn compute_bound(&mut self, threshold: Threshold, cost: Cost);
 }
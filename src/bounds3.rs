@@ -1,8 +1,10 @@
 use crate::bounds::RBound;
-use crate::corres::{Corres, CorresPair};
+use crate::corres::{Constraint, Threshold};
+use crate::trace::Cell;
+use crate::Cost;
 use glam::{Mat3A, Vec3, Vec3A};
 use std::cmp::Ordering;
-use std::f32::consts::{FRAC_PI_2, PI};
+use std::f32::consts::PI;
 use std::ops::RangeInclusive;
 
 const F32_2PI: f32 = 2.0 * PI;
@@ -14,20 +16,30 @@ pub struct Range {
 }
 
 pub struct RBAngleAxis<'a> {
-    upper: u32,
-    lower: u32,
+    upper: f32,
+    lower: f32,
     center: Vec3A,
     edge: f32,
-    corres_pairs: Vec<CorresPair<'a>>,
+    constraints: &'a [Constraint],
 }
 
 pub struct RBPolar<'a> {
-    upper: u32,
-    lower: u32,
+    upper: f32,
+    lower: f32,
     theta: Range,
     phi: Range,
     angle: Range,
-    corres_pairs: Vec<CorresPair<'a>>,
+    constraints: &'a [Constraint],
+}
+
+/// Rotation bound over a single angle about a fixed `axis`, applied after `base`
+pub struct RBAxis<'a> {
+    upper: f32,
+    lower: f32,
+    axis: Vec3A,
+    base: Mat3A,
+    angle: Range,
+    constraints: &'a [Constraint],
 }
 
 impl Range {
@@ -89,6 +101,12 @@ impl Ord for Range {
     }
 }
 
+impl From<Range> for [f32; 2] {
+    fn from(value: Range) -> Self {
+        [value.min, value.max]
+    }
+}
+
 impl From<RangeInclusive<f32>> for Range {
     fn from(value: RangeInclusive<f32>) -> Self {
         let (min, max) = value.into_inner();
@@ -98,22 +116,20 @@ impl From<RangeInclusive<f32>> for Range {
 }
 
 impl<'a> RBAngleAxis<'a> {
-    pub fn new(center: Vec3A, edge: f32, corres: &'a [Corres]) -> Self {
-        let corres_pairs = CorresPair::make_pairs(corres);
-
+    pub fn new(center: Vec3A, edge: f32, constraints: &'a [Constraint]) -> Self {
         RBAngleAxis {
-            upper: 0,
-            lower: 0,
+            upper: 0.0,
+            lower: 0.0,
             center,
             edge,
-            corres_pairs,
+            constraints,
         }
     }
 }
 
 impl<'a> PartialEq for RBAngleAxis<'a> {
     fn eq(&self, other: &Self) -> bool {
-        self.upper == other.upper
+        self.upper.total_cmp(&other.upper).is_eq()
     }
 }
 impl<'a> Eq for RBAngleAxis<'a> {}
@@ -125,7 +141,10 @@ impl<'a> PartialOrd for RBAngleAxis<'a> {
 }
 impl<'a> Ord for RBAngleAxis<'a> {
     fn cmp(&self, other: &Self) -> Ordering {
-        match (self.upper.cmp(&other.upper), self.lower.cmp(&other.lower)) {
+        match (
+            self.upper.total_cmp(&other.upper),
+            self.lower.total_cmp(&other.lower),
+        ) {
             (Ordering::Equal, lower_ordering) => lower_ordering,
             (upper_ordering, _) => upper_ordering,
         }
@@ -133,11 +152,11 @@ impl<'a> Ord for RBAngleAxis<'a> {
 }
 
 impl<'a> RBound for RBAngleAxis<'a> {
-    fn upper(&self) -> u32 {
+    fn upper(&self) -> f32 {
         self.upper
     }
 
-    fn lower(&self) -> u32 {
+    fn lower(&self) -> f32 {
         self.lower
     }
 
@@ -159,11 +178,11 @@ impl<'a> RBound for RBAngleAxis<'a> {
         center_points
             .into_iter()
             .map(|cp| RBAngleAxis {
-                upper: 0,
-                lower: 0,
+                upper: 0.0,
+                lower: 0.0,
                 center: cp.into(),
                 edge: half,
-                corres_pairs: self.corres_pairs.clone(),
+                constraints: self.constraints,
             })
             .collect()
     }
@@ -178,18 +197,22 @@ impl<'a> RBound for RBAngleAxis<'a> {
         Mat3A::from_axis_angle(axis, angle)
     }
 
-    fn compute_bound(&mut self, threshold: f32) {
-        for c_pair in &self.corres_pairs {
-            let angle = c_pair.uv().v_ru_angle(&self.rotation());
-            let error = (angle - FRAC_PI_2).abs();
+    fn cell(&self) -> Cell {
+        Cell::AngleAxis {
+            center: self.center,
+            edge: self.edge,
+        }
+    }
+
+    fn compute_bound(&mut self, threshold: Threshold, cost: Cost) {
+        for constraint in self.constraints {
+            let error = constraint.error(&self.rotation());
             let alpha = 3.0_f32.sqrt() * (self.edge / 2.0);
 
-            if error < threshold + alpha {
-                self.upper += 1;
-            }
-            if error < threshold {
-                self.lower += 1;
-            }
+            let threshold = constraint.threshold(threshold);
+
+            self.upper += constraint.weight() * cost.score((error - alpha).max(0.0), threshold);
+            self.lower += constraint.weight() * cost.score(error, threshold);
         }
     }
 }
@@ -199,27 +222,26 @@ impl<'a> RBPolar<'a> {
         theta: impl Into<Range>,
         phi: impl Into<Range>,
         angle: impl Into<Range>,
-        corres: &'a [Corres],
+        constraints: &'a [Constraint],
     ) -> Self {
         let theta = theta.into();
         let phi = phi.into();
         let angle = angle.into();
-        let corres_pairs = CorresPair::make_pairs(corres);
 
         RBPolar {
-            upper: 0,
-            lower: 0,
+            upper: 0.0,
+            lower: 0.0,
             theta,
             phi,
             angle,
-            corres_pairs,
+            constraints,
         }
     }
 }
 
 impl<'a> PartialEq for RBPolar<'a> {
     fn eq(&self, other: &Self) -> bool {
-        self.upper == other.upper
+        self.upper.total_cmp(&other.upper).is_eq()
     }
 }
 impl<'a> Eq for RBPolar<'a> {}
@@ -235,16 +257,16 @@ impl<'a> Ord for RBPolar<'a> {
         //     (Ordering::Equal, lower_ordering) => lower_ordering,
         //     (upper_ordering, _) => upper_ordering,
         // }
-        self.upper.cmp(&other.upper)
+        self.upper.total_cmp(&other.upper)
     }
 }
 
 impl<'a> RBound for RBPolar<'a> {
-    fn upper(&self) -> u32 {
+    fn upper(&self) -> f32 {
         self.upper
     }
 
-    fn lower(&self) -> u32 {
+    fn lower(&self) -> f32 {
         self.lower
     }
 
@@ -265,12 +287,12 @@ impl<'a> RBound for RBPolar<'a> {
         ];
         next.into_iter()
             .map(|(theta, phi, angle)| RBPolar {
-                upper: 0,
-                lower: 0,
+                upper: 0.0,
+                lower: 0.0,
                 theta,
                 phi,
                 angle,
-                corres_pairs: self.corres_pairs.clone(),
+                constraints: self.constraints,
             })
             .collect()
     }
@@ -284,15 +306,18 @@ impl<'a> RBound for RBPolar<'a> {
         Mat3A::from_axis_angle(axis, angle)
     }
 
-    fn compute_bound(&mut self, threshold: f32) {
-        for c_pair in &self.corres_pairs {
-            let uv = c_pair.uv();
-            let u = uv.u();
-            let v = uv.v();
+    fn cell(&self) -> Cell {
+        Cell::Polar {
+            theta: self.theta.into(),
+            phi: self.phi.into(),
+            angle: self.angle.into(),
+        }
+    }
+
+    fn compute_bound(&mut self, threshold: Threshold, cost: Cost) {
+        for constraint in self.constraints {
             // let angle = uv.v_ru_angle(&self.rotation());
-            let r0u = self.rotation() * *u;
-            let angle = v.angle_between(r0u);
-            let error = (angle - FRAC_PI_2).abs();
+            let error = constraint.error(&self.rotation());
             let alpha = {
                 // let r1 = self.angle.center();
                 // let r2 = self.angle.max;
@@ -342,12 +367,10 @@ impl<'a> RBound for RBPolar<'a> {
                 self.angle.length() * self.theta.length() * self.phi.length() / 8.0
             };
 
-            if error < threshold + alpha {
-                self.upper += 1;
-            }
-            if error < threshold {
-                self.lower += 1;
-            }
+            let threshold = constraint.threshold(threshold);
+
+            self.upper += constraint.weight() * cost.score((error - alpha).max(0.0), threshold);
+            self.lower += constraint.weight() * cost.score(error, threshold);
 
             // println!(
             //     "{:?}, {:?}, {:?}, upper={}, lower={}, alpha={}",
@@ -356,3 +379,97 @@ impl<'a> RBound for RBPolar<'a> {
         }
     }
 }
+
+impl<'a> RBAxis<'a> {
+    pub fn new(
+        axis: Vec3A,
+        base: Mat3A,
+        angle: impl Into<Range>,
+        constraints: &'a [Constraint],
+    ) -> Self {
+        let angle = angle.into();
+
+        RBAxis {
+            upper: 0.0,
+            lower: 0.0,
+            axis: axis.normalize(),
+            base,
+            angle,
+            constraints,
+        }
+    }
+}
+
+impl<'a> PartialEq for RBAxis<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.upper.total_cmp(&other.upper).is_eq()
+    }
+}
+impl<'a> Eq for RBAxis<'a> {}
+
+impl<'a> PartialOrd for RBAxis<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<'a> Ord for RBAxis<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (
+            self.upper.total_cmp(&other.upper),
+            self.lower.total_cmp(&other.lower),
+        ) {
+            (Ordering::Equal, lower_ordering) => lower_ordering,
+            (upper_ordering, _) => upper_ordering,
+        }
+    }
+}
+
+impl<'a> RBound for RBAxis<'a> {
+    fn upper(&self) -> f32 {
+        self.upper
+    }
+
+    fn lower(&self) -> f32 {
+        self.lower
+    }
+
+    fn subdivide(self) -> Vec<Self> {
+        let (a1, a2) = self.angle.divide();
+
+        [a1, a2]
+            .into_iter()
+            .map(|angle| RBAxis {
+                upper: 0.0,
+                lower: 0.0,
+                axis: self.axis,
+                base: self.base,
+                angle,
+                constraints: self.constraints,
+            })
+            .collect()
+    }
+
+    fn rotation(&self) -> Mat3A {
+        Mat3A::from_axis_angle(self.axis.into(), self.angle.center()) * self.base
+    }
+
+    fn cell(&self) -> Cell {
+        Cell::Axis {
+            angle: self.angle.into(),
+        }
+    }
+
+    fn compute_bound(&mut self, threshold: Threshold, cost: Cost) {
+        let rot = self.rotation();
+        for constraint in self.constraints {
+            let error = constraint.error(&rot);
+            // rotating about a fixed axis by δ moves any vector by at most δ
+            let alpha = self.angle.length() / 2.0;
+
+            let threshold = constraint.threshold(threshold);
+
+            self.upper += constraint.weight() * cost.score((error - alpha).max(0.0), threshold);
+            self.lower += constraint.weight() * cost.score(error, threshold);
+        }
+    }
+}
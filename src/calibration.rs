@@ -0,0 +1,393 @@
+//! OpenCV `FileStorage` calibration files
+//!
+//! Reads and writes the intrinsics as `camera_matrix` and the distortion as
+//! `distortion_coefficients`, the names used by OpenCV's calibration sample, in either the YAML
+//! or the XML flavor. `cameraMatrix`/`K` and `distCoeffs`/`D` are accepted as well, and so are
+//! ROS's `camera_info` YAML files, either the calibration files or the messages with their flat
+//! `K`/`k` and `D`/`d` lists, in flow or, as ROS 2 echoes them, block sequences.
+//!
+//! The coefficients are OpenCV's `k1 k2 p1 p2 [k3 [k4 k5 k6]]`, or `k1 k2 k3 k4` of the fisheye
+//! model when `distortion_model` is `fisheye` (or `equidistant`).
+
+use crate::{CameraK, Distortion};
+use glam::Mat3A;
+use std::fmt;
+use std::io::{self, Write};
+
+const CAMERA_MATRIX: [&str; 4] = ["camera_matrix", "cameraMatrix", "K", "k"];
+const DISTORTION: [&str; 4] = ["distortion_coefficients", "distCoeffs", "D", "d"];
+const DISTORTION_MODEL: &str = "distortion_model";
+
+/// Intrinsics and distortion of a calibrated camera
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Calibration {
+    pub k: CameraK,
+    pub distortion: Distortion,
+}
+
+#[derive(Debug)]
+pub enum CalibrationError {
+    Io(io::Error),
+    /// Malformed or missing node
+    Parse(String),
+    /// Distortion which cannot be represented by `Distortion`
+    Unsupported(String),
+}
+
+impl fmt::Display for CalibrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalibrationError::Io(error) => write!(f, "{error}"),
+            CalibrationError::Parse(message) => write!(f, "{message}"),
+            CalibrationError::Unsupported(message) => write!(f, "unsupported {message}"),
+        }
+    }
+}
+
+impl std::error::Error for CalibrationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CalibrationError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CalibrationError {
+    fn from(value: io::Error) -> Self {
+        CalibrationError::Io(value)
+    }
+}
+
+/// `FileStorage` node
+enum Node {
+    Scalar(String),
+    Matrix {
+        rows: usize,
+        cols: usize,
+        data: Vec<f32>,
+    },
+    /// Flow sequence of numbers, e.g. a matrix in row-major order
+    List(Vec<f32>),
+}
+
+impl Calibration {
+    /// Reads either flavor, telling XML by its leading `<`
+    pub fn read(text: &str) -> Result<Calibration, CalibrationError> {
+        if text.trim_start().starts_with('<') {
+            Calibration::read_xml(text)
+        } else {
+            Calibration::read_yaml(text)
+        }
+    }
+
+    pub fn read_yaml(text: &str) -> Result<Calibration, CalibrationError> {
+        Calibration::from_nodes(|name| yaml_node(text, name))
+    }
+
+    pub fn read_xml(text: &str) -> Result<Calibration, CalibrationError> {
+        Calibration::from_nodes(|name| xml_node(text, name))
+    }
+
+    pub fn write_yaml(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "%YAML:1.0")?;
+        writeln!(writer, "---")?;
+        let matrix = |writer: &mut dyn Write, name, rows, cols, data: &[f32]| {
+            writeln!(writer, "{name}: !!opencv-matrix")?;
+            writeln!(writer, "   rows: {rows}")?;
+            writeln!(writer, "   cols: {cols}")?;
+            writeln!(writer, "   dt: d")?;
+            let data: Vec<String> = data.iter().map(|v| format!("{v:?}")).collect();
+            writeln!(writer, "   data: [ {} ]", data.join(", "))
+        };
+
+        matrix(&mut writer, CAMERA_MATRIX[0], 3, 3, &self.camera_matrix())?;
+        let (model, coefficients) = self.coefficients();
+        if let Some(model) = model {
+            writeln!(writer, "{DISTORTION_MODEL}: {model}")?;
+        }
+        matrix(
+            &mut writer,
+            DISTORTION[0],
+            1,
+            coefficients.len(),
+            &coefficients,
+        )
+    }
+
+    pub fn write_xml(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "<?xml version=\"1.0\"?>")?;
+        writeln!(writer, "<opencv_storage>")?;
+        let matrix = |writer: &mut dyn Write, name, rows, cols, data: &[f32]| {
+            writeln!(writer, "<{name} type_id=\"opencv-matrix\">")?;
+            writeln!(writer, "  <rows>{rows}</rows>")?;
+            writeln!(writer, "  <cols>{cols}</cols>")?;
+            writeln!(writer, "  <dt>d</dt>")?;
+            let data: Vec<String> = data.iter().map(|v| format!("{v:?}")).collect();
+            writeln!(writer, "  <data>\n    {}</data></{name}>", data.join(" "))
+        };
+
+        matrix(&mut writer, CAMERA_MATRIX[0], 3, 3, &self.camera_matrix())?;
+        let (model, coefficients) = self.coefficients();
+        if let Some(model) = model {
+            writeln!(writer, "<{DISTORTION_MODEL}>{model}</{DISTORTION_MODEL}>")?;
+        }
+        matrix(
+            &mut writer,
+            DISTORTION[0],
+            1,
+            coefficients.len(),
+            &coefficients,
+        )?;
+        writeln!(writer, "</opencv_storage>")
+    }
+
+    fn from_nodes(
+        node: impl Fn(&str) -> Result<Option<Node>, CalibrationError>,
+    ) -> Result<Calibration, CalibrationError> {
+        let find = |names: &[&str]| -> Result<Option<Node>, CalibrationError> {
+            for name in names {
+                if let Some(found) = node(name)? {
+                    return Ok(Some(found));
+                }
+            }
+            Ok(None)
+        };
+
+        let k = match find(&CAMERA_MATRIX)? {
+            Some(
+                Node::Matrix {
+                    rows: 3,
+                    cols: 3,
+                    data,
+                }
+                | Node::List(data),
+            ) => {
+                let row_major: [f32; 9] = data
+                    .try_into()
+                    .map_err(|_| CalibrationError::Parse("camera matrix needs 9 values".into()))?;
+                CameraK::from(Mat3A::from_cols_array(&row_major).transpose())
+            }
+            Some(_) => return Err(CalibrationError::Parse("camera matrix is not 3x3".into())),
+            None => return Err(CalibrationError::Parse("missing camera matrix".into())),
+        };
+
+        let fisheye = match node(DISTORTION_MODEL)? {
+            Some(Node::Scalar(model)) => match model.trim_matches('"') {
+                "fisheye" | "equidistant" => true,
+                "plumb_bob" | "rational_polynomial" | "radtan" => false,
+                model => return Err(CalibrationError::Unsupported(format!("model `{model}`"))),
+            },
+            _ => false,
+        };
+        let coefficients = match find(&DISTORTION)? {
+            Some(Node::Matrix { data, .. } | Node::List(data)) => data,
+            Some(Node::Scalar(_)) => {
+                return Err(CalibrationError::Parse(
+                    "distortion coefficients are not a matrix".into(),
+                ))
+            }
+            None => vec![],
+        };
+
+        Ok(Calibration {
+            k,
+            distortion: distortion(&coefficients, fisheye)?,
+        })
+    }
+
+    fn camera_matrix(&self) -> [f32; 9] {
+        self.k.as_mat3a().transpose().to_cols_array()
+    }
+
+    /// The model name to store, if not the default one, and the coefficients
+    fn coefficients(&self) -> (Option<&'static str>, Vec<f32>) {
+        match self.distortion {
+            Distortion::None => (None, vec![0.0; 5]),
+            Distortion::BrownConrady { k, p } if k[3..].iter().all(|&v| v == 0.0) => {
+                (None, vec![k[0], k[1], p[0], p[1], k[2]])
+            }
+            Distortion::BrownConrady { k, p } => {
+                (None, vec![k[0], k[1], p[0], p[1], k[2], k[3], k[4], k[5]])
+            }
+            Distortion::Fisheye { k } => (Some("fisheye"), k.to_vec()),
+        }
+    }
+}
+
+fn distortion(coefficients: &[f32], fisheye: bool) -> Result<Distortion, CalibrationError> {
+    if fisheye {
+        let k = coefficients
+            .try_into()
+            .map_err(|_| CalibrationError::Parse("fisheye model needs 4 coefficients".into()))?;
+        return Ok(Distortion::Fisheye { k });
+    }
+
+    if coefficients.iter().all(|&v| v == 0.0) {
+        return Ok(Distortion::None);
+    }
+    if coefficients.len() < 4 {
+        return Err(CalibrationError::Parse(
+            "distortion needs at least 4 coefficients".into(),
+        ));
+    }
+    // thin prism and tilt coefficients follow the first 8
+    if coefficients.iter().skip(8).any(|&v| v != 0.0) {
+        return Err(CalibrationError::Unsupported(
+            "thin prism or tilted distortion".into(),
+        ));
+    }
+
+    let c = |i: usize| coefficients.get(i).copied().unwrap_or(0.0);
+    Ok(Distortion::BrownConrady {
+        k: [c(0), c(1), c(4), c(5), c(6), c(7)],
+        p: [c(2), c(3)],
+    })
+}
+
+/// Finds the top-level YAML node `name`
+fn yaml_node(text: &str, name: &str) -> Result<Option<Node>, CalibrationError> {
+    let mut lines = text.lines().peekable();
+    let prefix = format!("{name}:");
+    let Some(value) = lines
+        .by_ref()
+        .find_map(|line| line.strip_prefix(prefix.as_str()))
+    else {
+        return Ok(None);
+    };
+
+    // ROS's `camera_info` messages store the matrices as flat lists, which may wrap
+    let value = value.trim();
+    if value.starts_with('[') {
+        let mut list = value.to_string();
+        while !list.contains(']') {
+            let Some(line) = lines.next() else {
+                break;
+            };
+            list.push_str(line);
+        }
+        return list_node(name, &list).map(Some);
+    }
+
+    // ROS 2 echoes them as block sequences, one `- v` item per line
+    let is_item = |line: &&str| line.trim_start().starts_with('-');
+    if value.is_empty() && lines.peek().is_some_and(is_item) {
+        let items: Vec<&str> = std::iter::from_fn(|| lines.next_if(is_item))
+            .map(|line| line.trim_start()[1..].trim())
+            .collect();
+        return list_node(name, &format!("[{}]", items.join(","))).map(Some);
+    }
+
+    // and its calibration files store them untagged
+    if !value.is_empty() && !value.starts_with("!!opencv-matrix") {
+        return Ok(Some(Node::Scalar(value.to_string())));
+    }
+
+    // the indented block of the matrix
+    let block: Vec<&str> = lines
+        .take_while(|line| line.starts_with(char::is_whitespace))
+        .collect();
+    let block = block.join("\n");
+    let field = |key: &str| -> Result<&str, CalibrationError> {
+        block
+            .split_once(&format!("{key}:"))
+            .map(|(_, rest)| rest)
+            .ok_or_else(|| CalibrationError::Parse(format!("`{name}` has no `{key}`")))
+    };
+    let first_token = |key: &str| -> Result<&str, CalibrationError> {
+        Ok(field(key)?.split_whitespace().next().unwrap_or(""))
+    };
+
+    let data = field("data")?;
+    let data = data
+        .split_once('[')
+        .and_then(|(_, rest)| rest.split_once(']'))
+        .map(|(data, _)| data)
+        .ok_or_else(|| CalibrationError::Parse(format!("`{name}` has malformed data")))?;
+
+    matrix(
+        name,
+        first_token("rows")?,
+        first_token("cols")?,
+        data.split(',').map(str::trim).filter(|v| !v.is_empty()),
+    )
+    .map(Some)
+}
+
+/// Parses the flow sequence `[v0, v1, ...]`
+fn list_node(name: &str, list: &str) -> Result<Node, CalibrationError> {
+    let invalid = || CalibrationError::Parse(format!("`{name}` has a malformed list"));
+    let (_, rest) = list.split_once('[').ok_or_else(invalid)?;
+    let (values, _) = rest.split_once(']').ok_or_else(invalid)?;
+    let data = values
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse::<f64>().map(|v| v as f32))
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+
+    Ok(Node::List(data))
+}
+
+/// Finds the XML node `name`
+fn xml_node(text: &str, name: &str) -> Result<Option<Node>, CalibrationError> {
+    let Some(content) = xml_element(text, name) else {
+        return Ok(None);
+    };
+    if !content.contains("<data>") {
+        return Ok(Some(Node::Scalar(content.trim().to_string())));
+    }
+
+    let field = |key: &str| {
+        xml_element(content, key)
+            .ok_or_else(|| CalibrationError::Parse(format!("`{name}` has no `{key}`")))
+    };
+
+    matrix(
+        name,
+        field("rows")?.trim(),
+        field("cols")?.trim(),
+        field("data")?.split_whitespace(),
+    )
+    .map(Some)
+}
+
+/// Content of the first element `<name ...>...</name>`
+fn xml_element<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{name}");
+    let mut start = 0;
+    loop {
+        let found = start + text[start..].find(&open)?;
+        let rest = &text[found + open.len()..];
+        // skips elements whose names merely begin with `name`
+        if rest.starts_with(['>', ' ', '\t', '\n', '\r']) {
+            let content = &rest[rest.find('>')? + 1..];
+            return content
+                .find(&format!("</{name}>"))
+                .map(|end| &content[..end]);
+        }
+        start = found + open.len();
+    }
+}
+
+fn matrix<'a>(
+    name: &str,
+    rows: &str,
+    cols: &str,
+    data: impl Iterator<Item = &'a str>,
+) -> Result<Node, CalibrationError> {
+    let invalid = |what: &str| CalibrationError::Parse(format!("`{name}` has invalid {what}"));
+    let rows = rows.parse().map_err(|_| invalid("rows"))?;
+    let cols = cols.parse().map_err(|_| invalid("cols"))?;
+    let data: Vec<f32> = data
+        .map(|v| v.parse::<f64>().map(|v| v as f32))
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid("data"))?;
+    if data.len() != rows * cols {
+        return Err(invalid("data size"));
+    }
+
+    Ok(Node::Matrix { rows, cols, data })
+}
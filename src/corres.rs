@@ -1,5 +1,13 @@
 use crate::types::{CCoord, WCoord};
+use crate::{Cost, KindSettings};
 use glam::{Mat3A, Vec3A};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+/// Relative distance of world points, and sine of the angle of rays, below which they coincide
+pub const COINCIDENCE: f32 = 1e-5;
+/// Angular deviation of a unit pixel under a focal length of 1000 pixels, taken as the noise of
+/// correspondences built without a camera
+const DEFAULT_NOISE: f32 = 1e-3;
 
 #[derive(Clone, Copy, Debug)]
 pub struct UV {
@@ -24,15 +32,82 @@ impl UV {
     }
 }
 
+/// Inlier threshold of a constraint
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Threshold {
+    /// Fixed angle in radians
+    Angle(f32),
+    /// Multiple of the correspondences' noise, i.e. pixels for a unit pixel covariance
+    Pixel(f32),
+}
+
+impl Threshold {
+    pub fn value(self) -> f32 {
+        match self {
+            Threshold::Angle(value) | Threshold::Pixel(value) => value,
+        }
+    }
+
+    /// Same kind of threshold with another value
+    pub fn with_value(self, value: f32) -> Self {
+        match self {
+            Threshold::Angle(_) => Threshold::Angle(value),
+            Threshold::Pixel(_) => Threshold::Pixel(value),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Corres {
     projected: CCoord,
     world: WCoord,
+    weight: f32,
+    noise: f32,
 }
 
 impl Corres {
     pub fn new(projected: CCoord, world: WCoord) -> Self {
-        Corres { projected, world }
+        Corres {
+            projected,
+            world,
+            weight: 1.0,
+            noise: DEFAULT_NOISE,
+        }
+    }
+
+    /// Sets the confidence of this correspondence (e.g. a descriptor match score)
+    pub fn with_weight(self, weight: f32) -> Self {
+        debug_assert!(weight >= 0.0, "weight must be non-negative: {weight}");
+
+        Corres { weight, ..self }
+    }
+
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+
+    /// Sets the angular deviation of the projected ray per unit of `Threshold::Pixel`, which is
+    /// `DEFAULT_NOISE` unless set
+    pub fn with_noise(self, noise: f32) -> Self {
+        Corres { noise, ..self }
+    }
+
+    /// Angular threshold for the observed ray
+    pub fn threshold(&self, threshold: Threshold) -> f32 {
+        match threshold {
+            Threshold::Angle(angle) => angle,
+            Threshold::Pixel(pixel) => pixel * self.noise,
+        }
+    }
+
+    pub fn projected(&self) -> &CCoord {
+        &self.projected
+    }
+
+    pub fn world(&self) -> &WCoord {
+        &self.world
     }
 
     pub fn compute_uv(&self, other: &Self) -> UV {
@@ -53,18 +128,323 @@ impl Corres {
 
 /// Represents two correspondences pair
 #[derive(Clone, Copy, Debug)]
-pub struct CorresPair<'a>(&'a Corres, &'a Corres);
+pub struct CorresPair<'a> {
+    first: &'a Corres,
+    second: &'a Corres,
+    degenerate: bool,
+}
 
 impl<'a> CorresPair<'a> {
+    pub fn new(first: &'a Corres, second: &'a Corres) -> Self {
+        let world_scale = first
+            .world
+            .as_vec3a()
+            .length()
+            .max(second.world.as_vec3a().length())
+            .max(1.0);
+        let coincident =
+            (first.world - second.world).as_vec3a().length() <= COINCIDENCE * world_scale;
+        let (p0, p1) = (first.projected.as_vec3a(), second.projected.as_vec3a());
+        let sin = p0.cross(p1).length() / (p0.length() * p1.length());
+
+        CorresPair {
+            first,
+            second,
+            // a null ray gives a NaN sine
+            degenerate: coincident || sin.is_nan() || sin <= COINCIDENCE,
+        }
+    }
+
     /// Generates correspondence pairs from vector of correspondence
     pub fn make_pairs(corres: &'a [Corres]) -> Vec<CorresPair<'a>> {
         corres
             .chunks_exact(2)
-            .map(|c| CorresPair(&c[0], &c[1]))
+            .map(|c| CorresPair::new(&c[0], &c[1]))
             .collect()
     }
 
     pub fn uv(&self) -> UV {
-        self.0.compute_uv(self.1)
+        self.first.compute_uv(self.second)
+    }
+
+    /// Rotation constraint of the pair, to be bounded along with those of lines
+    ///
+    /// Deviating one ray by δ tilts the plane spanned by both rays by up to δ / sin∠(rays), so
+    /// noisy or nearly parallel rays loosen the constraint.
+    pub fn constraint(&self) -> Constraint {
+        let (p0, p1) = (
+            self.first.projected.as_vec3a(),
+            self.second.projected.as_vec3a(),
+        );
+        let sin = p0.cross(p1).length() / (p0.length() * p1.length());
+
+        Constraint {
+            uv: self.uv(),
+            relation: Relation::Orthogonal,
+            weight: self.weight(),
+            noise: (self.first.noise + self.second.noise) / sin.max(f32::EPSILON),
+            threshold: None,
+        }
+    }
+
+    /// Whether the world points coincide or the rays are parallel, leaving `uv` without direction
+    pub fn is_degenerate(&self) -> bool {
+        self.degenerate
+    }
+
+    /// Weight of the pair's constraint, which holds only if both correspondences are correct
+    ///
+    /// Degenerate pairs constrain nothing and weigh zero.
+    pub fn weight(&self) -> f32 {
+        if self.degenerate {
+            0.0
+        } else {
+            self.first.weight * self.second.weight
+        }
+    }
+}
+
+/// Correspondence of an image line with a world line, each given by two points on it
+///
+/// The points need not correspond: the image points may be the ends of a detected segment, and
+/// the world points any two points of the world line.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineCorres {
+    projected: [CCoord; 2],
+    world: [WCoord; 2],
+    weight: f32,
+    noise: f32,
+}
+
+impl LineCorres {
+    pub fn new(projected: [CCoord; 2], world: [WCoord; 2]) -> Self {
+        LineCorres {
+            projected,
+            world,
+            weight: 1.0,
+            noise: DEFAULT_NOISE,
+        }
+    }
+
+    /// Sets the confidence of this correspondence
+    pub fn with_weight(self, weight: f32) -> Self {
+        debug_assert!(weight >= 0.0, "weight must be non-negative: {weight}");
+
+        LineCorres { weight, ..self }
+    }
+
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+
+    /// Sets the angular deviation of the projected rays per unit of `Threshold::Pixel`, which is
+    /// `DEFAULT_NOISE` unless set
+    pub fn with_noise(self, noise: f32) -> Self {
+        LineCorres { noise, ..self }
+    }
+
+    /// Angular threshold of the world points' distance to the back-projected plane
+    pub fn threshold(&self, threshold: Threshold) -> f32 {
+        match threshold {
+            Threshold::Angle(angle) => angle,
+            Threshold::Pixel(pixel) => pixel * self.noise,
+        }
+    }
+
+    pub fn projected(&self) -> &[CCoord; 2] {
+        &self.projected
+    }
+
+    pub fn world(&self) -> &[WCoord; 2] {
+        &self.world
+    }
+
+    /// Normal of the plane through the camera center and the image line
+    pub fn normal(&self) -> Vec3A {
+        self.projected[0].cross(self.projected[1]).as_vec3a()
+    }
+
+    /// Whether the world points coincide or the rays are parallel, leaving the line undefined
+    pub fn is_degenerate(&self) -> bool {
+        let [w0, w1] = self.world.map(WCoord::as_vec3a);
+        let world_scale = w0.length().max(w1.length()).max(1.0);
+        let [p0, p1] = self.projected.map(CCoord::as_vec3a);
+        let sin = p0.cross(p1).length() / (p0.length() * p1.length());
+
+        w0.distance(w1) <= COINCIDENCE * world_scale || sin.is_nan() || sin <= COINCIDENCE
+    }
+
+    /// Rotation constraint of the line: its world direction lies in the back-projected plane
+    ///
+    /// Deviating one ray by δ tilts the plane by up to δ / sin∠(rays), as for a pair.
+    pub fn constraint(&self) -> Constraint {
+        let [p0, p1] = self.projected.map(CCoord::as_vec3a);
+        let sin = p0.cross(p1).length() / (p0.length() * p1.length());
+
+        Constraint {
+            uv: UV {
+                u: (self.world[1] - self.world[0]).as_vec3a(),
+                v: self.normal(),
+            },
+            relation: Relation::Orthogonal,
+            weight: if self.is_degenerate() {
+                0.0
+            } else {
+                self.weight
+            },
+            noise: 2.0 * self.noise / sin.max(f32::EPSILON),
+            threshold: None,
+        }
+    }
+
+    /// Largest angle between the back-projected plane and the world points under the pose, or π
+    /// if one is behind the camera
+    pub fn error(&self, rot: &Mat3A, trans: Vec3A) -> f32 {
+        let normal = self.normal();
+
+        self.world
+            .iter()
+            .map(|w| *rot * w.as_vec3a() + trans)
+            .map(|x| {
+                if x.z <= 0.0 {
+                    return PI;
+                }
+                (normal.angle_between(x) - FRAC_PI_2).abs()
+            })
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Correspondence of a vanishing point with the world direction of the parallel lines meeting
+/// there
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VanishingCorres {
+    projected: CCoord,
+    direction: Vec3A,
+    weight: f32,
+    noise: f32,
+}
+
+impl VanishingCorres {
+    pub fn new(projected: CCoord, direction: Vec3A) -> Self {
+        VanishingCorres {
+            projected,
+            direction,
+            weight: 1.0,
+            noise: DEFAULT_NOISE,
+        }
+    }
+
+    /// Sets the confidence of this correspondence
+    pub fn with_weight(self, weight: f32) -> Self {
+        debug_assert!(weight >= 0.0, "weight must be non-negative: {weight}");
+
+        VanishingCorres { weight, ..self }
+    }
+
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+
+    /// Sets the angular deviation of the projected ray per unit of `Threshold::Pixel`, which is
+    /// `DEFAULT_NOISE` unless set
+    pub fn with_noise(self, noise: f32) -> Self {
+        VanishingCorres { noise, ..self }
+    }
+
+    pub fn projected(&self) -> &CCoord {
+        &self.projected
+    }
+
+    pub fn direction(&self) -> &Vec3A {
+        &self.direction
+    }
+
+    /// Whether the direction or the ray is zero, leaving the rotation unconstrained
+    pub fn is_degenerate(&self) -> bool {
+        self.direction.length_squared() == 0.0 || self.projected.as_vec3a().length_squared() == 0.0
+    }
+
+    /// Rotation constraint of the direction: it is turned onto the ray, up to its sign
+    pub fn constraint(&self) -> Constraint {
+        Constraint {
+            uv: UV {
+                u: self.direction,
+                v: self.projected.as_vec3a(),
+            },
+            relation: Relation::Parallel,
+            weight: if self.is_degenerate() {
+                0.0
+            } else {
+                self.weight
+            },
+            noise: self.noise,
+            threshold: None,
+        }
+    }
+}
+
+/// Relation of `v` and `Ru` which a constraint requires
+#[derive(Clone, Copy, Debug)]
+pub enum Relation {
+    Orthogonal,
+    /// Parallel or anti-parallel
+    Parallel,
+}
+
+/// Rotation constraint relating `v` and `Ru`, of a correspondence pair, a line or a vanishing
+/// point, as bounded by the search
+///
+/// Its error moves by at most the angle `Ru` moves, which is what the bounds rely on.
+#[derive(Clone, Copy, Debug)]
+pub struct Constraint {
+    uv: UV,
+    relation: Relation,
+    weight: f32,
+    /// Angular deviation of the constraint per unit of `Threshold::Pixel`
+    noise: f32,
+    /// Value replacing the search threshold's
+    threshold: Option<f32>,
+}
+
+impl Constraint {
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+
+    /// Applies the weight and threshold of the constraint's kind
+    pub fn with_settings(self, settings: KindSettings) -> Self {
+        Constraint {
+            weight: self.weight * settings.weight,
+            threshold: settings.threshold,
+            ..self
+        }
+    }
+
+    pub fn threshold(&self, threshold: Threshold) -> f32 {
+        match threshold.with_value(self.threshold.unwrap_or(threshold.value())) {
+            Threshold::Angle(angle) => angle,
+            Threshold::Pixel(pixel) => pixel * self.noise,
+        }
+    }
+
+    /// Angular error of the constraint under `rot`
+    pub fn error(&self, rot: &Mat3A) -> f32 {
+        let deviation = (self.uv.v_ru_angle(rot) - FRAC_PI_2).abs();
+
+        match self.relation {
+            Relation::Orthogonal => deviation,
+            Relation::Parallel => FRAC_PI_2 - deviation,
+        }
+    }
+
+    /// Sums the weighted scores of the constraints under `rot`
+    pub fn score(constraints: &[Constraint], rot: &Mat3A, threshold: Threshold, cost: Cost) -> f32 {
+        constraints
+            .iter()
+            .map(|c| c.weight * cost.score(c.error(rot), c.threshold(threshold)))
+            .sum()
     }
 }
@@ -0,0 +1,296 @@
+//! Detection of correspondence configurations which determine no unique pose
+
+use crate::corres::{Corres, CorresPair, LineCorres, VanishingCorres, COINCIDENCE};
+use crate::linalg;
+use glam::{DVec3, Vec3A};
+
+/// Spread across a line, relative to the spread along it, below which points are collinear
+const COLLINEAR: f64 = 1e-3;
+/// Spread across a plane, relative to the largest spread, below which points are planar
+const PLANAR: f64 = 1e-2;
+/// Sine of the angle between two directions above which they are apart, e.g. for vanishing
+/// directions to fix the rotation about the line of the world points
+const CROSSING: f64 = 0.1;
+/// Angular radius in radians of the rays under which a planar target is seen nearly
+/// orthographically
+const NARROW_VIEW: f32 = 0.1;
+
+/// Degenerate constraints of the correspondences, found before solving
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Degeneracy {
+    /// Pairs, of correspondences `2 * i` and `2 * i + 1`, whose world points coincide or whose
+    /// rays are parallel; the rotation search ignores them
+    pub pairs: Vec<usize>,
+    /// Line correspondences whose world points coincide or whose rays are parallel; the search
+    /// ignores them as well
+    pub lines: Vec<usize>,
+    pub configuration: Option<Configuration>,
+}
+
+/// Degeneracy of the whole set of world points
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Configuration {
+    /// Fewer than three distinct world points, which leave the pose undetermined unless there are
+    /// two and vanishing directions fix the rotation about their line, as for `Collinear`
+    TooFewPoints,
+    /// World points on a line, which leave the rotation about it undetermined unless two
+    /// vanishing directions apart from each other both cross it
+    ///
+    /// The rays of the points then lie in a single plane, so a single direction leaves several
+    /// rotations satisfying every constraint.
+    Collinear,
+    /// World points on the line of direction `line`, or two of them, whose rotation about it the
+    /// vanishing directions fix
+    ///
+    /// The constraints hold about equally for the rotation turned by π about the normal of the
+    /// line and a direction, or of two directions; the solver keeps the one best supported by the
+    /// points, and fails if none is.
+    FixedLine { line: Vec3A },
+    /// Planar world points seen edge-on from a camera in their plane, whose image points on a
+    /// line leave the rotation undetermined
+    EdgeOn,
+    /// World points on the plane of `normal`
+    ///
+    /// The pair constraints hold equally for the rotation turned by π about `normal`; the solver
+    /// keeps the one putting most points in front of the camera.
+    Planar { normal: Vec3A },
+    /// Planar world points seen under a narrow angle, where two poses tilted the opposite way
+    /// about the line of sight also explain the image points about equally well
+    PlanarAmbiguity { normal: Vec3A },
+}
+
+impl Configuration {
+    /// Whether no pose can be determined
+    pub fn is_fatal(self) -> bool {
+        matches!(
+            self,
+            Configuration::TooFewPoints | Configuration::Collinear | Configuration::EdgeOn
+        )
+    }
+}
+
+impl Degeneracy {
+    /// Checks the correspondences, with the points of the lines counted as world points and the
+    /// vanishing directions fixing rotations about them
+    pub(crate) fn check(
+        corres: &[Corres],
+        lines: &[LineCorres],
+        vanishing: &[VanishingCorres],
+    ) -> Self {
+        let pairs = CorresPair::make_pairs(corres)
+            .iter()
+            .enumerate()
+            .filter(|(_, c_pair)| c_pair.is_degenerate())
+            .map(|(i, _)| i)
+            .collect();
+
+        let degenerate_lines = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.is_degenerate())
+            .map(|(i, _)| i)
+            .collect();
+        let observations: Vec<(Vec3A, DVec3)> = corres
+            .iter()
+            .map(|c| (c.projected().as_vec3a(), c.world().as_vec3a().as_dvec3()))
+            .chain(lines.iter().flat_map(|line| {
+                let [p0, p1] = line.projected().map(|p| p.as_vec3a());
+                let [w0, w1] = line.world().map(|w| w.as_vec3a().as_dvec3());
+                [(p0, w0), (p1, w1)]
+            }))
+            .collect();
+        let directions: Vec<DVec3> = directions(vanishing).collect();
+
+        Degeneracy {
+            pairs,
+            lines: degenerate_lines,
+            configuration: configuration(&observations, &directions),
+        }
+    }
+
+    /// Whether no pose can be determined, in which case none is estimated
+    pub fn is_fatal(&self) -> bool {
+        self.configuration.is_some_and(Configuration::is_fatal)
+    }
+
+    /// Axes about which turning the rotation by π keeps the constraints about equally satisfied
+    pub(crate) fn turns(&self, vanishing: &[VanishingCorres]) -> Vec<Vec3A> {
+        match self.configuration {
+            Some(Configuration::Planar { normal } | Configuration::PlanarAmbiguity { normal }) => {
+                vec![normal]
+            }
+            Some(Configuration::FixedLine { line }) => {
+                let line = line.as_dvec3();
+                let axes: Vec<DVec3> = std::iter::once(line)
+                    .chain(directions(vanishing).filter(|&d| apart(d, line)))
+                    .collect();
+
+                axes.iter()
+                    .enumerate()
+                    .flat_map(|(i, &a)| axes[i + 1..].iter().map(move |&b| a.cross(b)))
+                    .filter_map(|axis| axis.try_normalize())
+                    .map(|axis| axis.as_vec3a())
+                    .collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Normal of the plane of the world points, if they are planar
+    pub fn plane_normal(&self) -> Option<Vec3A> {
+        match self.configuration {
+            Some(Configuration::Planar { normal } | Configuration::PlanarAmbiguity { normal }) => {
+                Some(normal)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Whether the world points lie on a plane of `normal`, within the spread `PLANAR` allows
+pub(crate) fn on_plane(corres: &[Corres], normal: Vec3A) -> bool {
+    let Some(normal) = normal.as_dvec3().try_normalize() else {
+        return false;
+    };
+    let world: Vec<DVec3> = corres
+        .iter()
+        .map(|c| c.world().as_vec3a().as_dvec3())
+        .collect();
+    if world.is_empty() {
+        return false;
+    }
+
+    let center = world.iter().sum::<DVec3>() / world.len() as f64;
+    let (spreads, _) = principal_spreads(world.iter().map(|w| *w - center));
+    let across = (world
+        .iter()
+        .map(|w| (*w - center).dot(normal).powi(2))
+        .sum::<f64>()
+        / world.len() as f64)
+        .sqrt();
+
+    across <= PLANAR * spreads[2]
+}
+
+/// Configuration of the rays observing the world points, given the vanishing `directions`
+fn configuration(observations: &[(Vec3A, DVec3)], directions: &[DVec3]) -> Option<Configuration> {
+    let world: Vec<DVec3> = observations.iter().map(|(_, world)| *world).collect();
+
+    let scale = world.iter().fold(1.0_f64, |m, w| m.max(w.length()));
+    let mut distinct: Vec<DVec3> = vec![];
+    for w in &world {
+        if distinct.len() == 3 {
+            break;
+        }
+        if distinct
+            .iter()
+            .all(|d| d.distance(*w) > COINCIDENCE as f64 * scale)
+        {
+            distinct.push(*w);
+        }
+    }
+    if distinct.len() < 3 {
+        // two points fix the translation once the rotation about their line is fixed
+        if let [a, b] = distinct[..] {
+            if fix_rotation(directions, b - a) {
+                let line = (b - a).normalize().as_vec3a();
+                return Some(Configuration::FixedLine { line });
+            }
+        }
+        return Some(Configuration::TooFewPoints);
+    }
+
+    // principal spreads of the world points, ascending
+    let center = world.iter().sum::<DVec3>() / world.len() as f64;
+    let (spreads, axes) = principal_spreads(world.iter().map(|w| *w - center));
+
+    if spreads[1] <= COLLINEAR * spreads[2] {
+        let line = DVec3::from(axes[2]);
+        if fix_rotation(directions, line) {
+            let line = line.as_vec3a();
+            return Some(Configuration::FixedLine { line });
+        }
+        return Some(Configuration::Collinear);
+    }
+    if spreads[0] > PLANAR * spreads[2] {
+        return None;
+    }
+
+    // rays in a plane through the camera center, spread across their mean direction
+    let rays = observations
+        .iter()
+        .filter_map(|(ray, _)| ray.as_dvec3().try_normalize());
+    let (ray_spreads, _) = principal_spreads(rays);
+    if ray_spreads[0] <= PLANAR * ray_spreads[1] {
+        return Some(Configuration::EdgeOn);
+    }
+
+    let normal = DVec3::from(axes[0]).as_vec3a();
+    if view_radius(observations) < NARROW_VIEW {
+        Some(Configuration::PlanarAmbiguity { normal })
+    } else {
+        Some(Configuration::Planar { normal })
+    }
+}
+
+/// Directions of the vanishing correspondences which constrain the rotation
+fn directions(vanishing: &[VanishingCorres]) -> impl Iterator<Item = DVec3> + '_ {
+    vanishing
+        .iter()
+        .filter(|vanishing| !vanishing.is_degenerate())
+        .map(|vanishing| vanishing.direction().as_dvec3())
+}
+
+/// Whether two of the `directions` crossing `line` are apart, fixing the rotation about it
+fn fix_rotation(directions: &[DVec3], line: DVec3) -> bool {
+    let crossing: Vec<DVec3> = directions
+        .iter()
+        .copied()
+        .filter(|&d| apart(d, line))
+        .collect();
+
+    crossing
+        .iter()
+        .enumerate()
+        .any(|(i, &a)| crossing[i + 1..].iter().any(|&b| apart(a, b)))
+}
+
+/// Whether the angle between `a` and `b`, up to sign, exceeds `CROSSING`'s
+fn apart(a: DVec3, b: DVec3) -> bool {
+    a.cross(b).length() > CROSSING * a.length() * b.length()
+}
+
+/// Principal root mean squares of the vectors, ascending, and their axes as rows
+fn principal_spreads(vectors: impl Iterator<Item = DVec3>) -> ([f64; 3], [[f64; 3]; 3]) {
+    let mut n = 0.0;
+    let mut moments = [[0.0; 3]; 3];
+    for d in vectors {
+        let d = d.to_array();
+        for (row, a) in moments.iter_mut().zip(d) {
+            for (value, b) in row.iter_mut().zip(d) {
+                *value += a * b;
+            }
+        }
+        n += 1.0;
+    }
+    let (variances, axes) = linalg::sym_eigen(moments);
+
+    (variances.map(|v| (v.max(0.0) / n).sqrt()), axes)
+}
+
+/// Largest angle between a ray and the mean ray direction
+fn view_radius(observations: &[(Vec3A, DVec3)]) -> f32 {
+    let rays: Vec<Vec3A> = observations
+        .iter()
+        .filter_map(|(ray, _)| ray.try_normalize())
+        .collect();
+    let Some(mean) = rays.iter().sum::<Vec3A>().try_normalize() else {
+        return std::f32::consts::PI;
+    };
+
+    rays.iter()
+        .map(|ray| ray.angle_between(mean))
+        .fold(0.0, f32::max)
+}
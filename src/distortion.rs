@@ -0,0 +1,74 @@
+use crate::types::ICoord;
+use crate::CameraK;
+use glam::Vec2;
+
+const UNDISTORT_ITERATIONS: usize = 20;
+
+/// Lens distortion, acting on normalized image coordinates
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Distortion {
+    #[default]
+    None,
+    /// OpenCV's rational model: radial `k[0..3]` over `k[3..6]`, and tangential `p`
+    ///
+    /// The common 5-coefficient model has `k[3..6]` zero.
+    BrownConrady { k: [f32; 6], p: [f32; 2] },
+    /// Equidistant fisheye model (OpenCV's `fisheye`)
+    Fisheye { k: [f32; 4] },
+}
+
+impl Distortion {
+    pub fn distort(self, point: Vec2) -> Vec2 {
+        match self {
+            Distortion::None => point,
+            Distortion::BrownConrady { k, p } => {
+                let r2 = point.length_squared();
+                let radial = (1.0 + r2 * (k[0] + r2 * (k[1] + r2 * k[2])))
+                    / (1.0 + r2 * (k[3] + r2 * (k[4] + r2 * k[5])));
+                let xy = point.x * point.y;
+                let tangential = Vec2::new(
+                    2.0 * p[0] * xy + p[1] * (r2 + 2.0 * point.x * point.x),
+                    p[0] * (r2 + 2.0 * point.y * point.y) + 2.0 * p[1] * xy,
+                );
+
+                point * radial + tangential
+            }
+            Distortion::Fisheye { k } => {
+                let r = point.length();
+                if r < f32::EPSILON {
+                    return point;
+                }
+                let theta = r.atan();
+                let theta2 = theta * theta;
+                let distorted = theta
+                    * (1.0 + theta2 * (k[0] + theta2 * (k[1] + theta2 * (k[2] + theta2 * k[3]))));
+
+                point * (distorted / r)
+            }
+        }
+    }
+
+    /// Inverts `distort` by fixed-point iteration
+    pub fn undistort(self, point: Vec2) -> Vec2 {
+        if self == Distortion::None {
+            return point;
+        }
+
+        let mut undistorted = point;
+        for _ in 0..UNDISTORT_ITERATIONS {
+            undistorted += point - self.distort(undistorted);
+        }
+
+        undistorted
+    }
+
+    /// Undistorts a pixel of the image taken by a camera with the intrinsics `k`
+    pub fn undistort_pixel(self, k: &CameraK, pixel: Vec2) -> ICoord {
+        let focal = Vec2::new(k.fx, k.fy);
+        let center = Vec2::new(k.cx, k.cy);
+        let undistorted = self.undistort((pixel - center) / focal) * focal + center;
+
+        undistorted.round().as_ivec2().into()
+    }
+}
@@ -0,0 +1,200 @@
+use crate::corres::{Corres, Threshold};
+use crate::types::{ICoord, WCoord};
+use crate::{CameraK, Solution};
+use glam::Mat2;
+
+/// Pose estimator fed with 2D-3D correspondences, so estimators can be swapped, wrapped and
+/// benchmarked uniformly
+pub trait PoseEstimator {
+    fn reset_correspondence(&mut self);
+
+    fn reserve_correspondence(&mut self, n: usize);
+
+    /// Adds a correspondence whose image point has the noise `covariance` in pixels²
+    fn add_correspondence_with_covariance(
+        &mut self,
+        projected: ICoord,
+        world: WCoord,
+        k: &CameraK,
+        weight: f32,
+        covariance: Mat2,
+    );
+
+    /// Estimates the pose from the correspondences added so far
+    fn estimate(&self) -> Solution;
+
+    /// Re-selects the inliers of `solution` at its refined pose and refines it again on them,
+    /// returning whether they changed
+    fn reselect(&self, solution: &mut Solution) -> bool;
+
+    fn add_correspondence(&mut self, projected: ICoord, world: WCoord, k: &CameraK) {
+        self.add_weighted_correspondence(projected, world, k, 1.0);
+    }
+
+    /// Adds a correspondence counted in proportion to `weight`
+    fn add_weighted_correspondence(
+        &mut self,
+        projected: ICoord,
+        world: WCoord,
+        k: &CameraK,
+        weight: f32,
+    ) {
+        self.add_correspondence_with_covariance(projected, world, k, weight, Mat2::IDENTITY);
+    }
+
+    fn add_correspondences<I>(&mut self, corres: I, k: &CameraK)
+    where
+        Self: Sized,
+        I: IntoIterator<Item = (ICoord, WCoord)>,
+    {
+        self.add_weighted_correspondences(
+            corres
+                .into_iter()
+                .map(|(projected, world)| (projected, world, 1.0)),
+            k,
+        );
+    }
+
+    fn add_weighted_correspondences<I>(&mut self, corres: I, k: &CameraK)
+    where
+        Self: Sized,
+        I: IntoIterator<Item = (ICoord, WCoord, f32)>,
+    {
+        let corres = corres.into_iter();
+        self.reserve_correspondence(corres.size_hint().0);
+
+        for (projected, world, weight) in corres {
+            self.add_weighted_correspondence(projected, world, k, weight);
+        }
+    }
+}
+
+/// Point correspondences and thresholds common to the solvers
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Setup {
+    pub(crate) corres: Vec<Corres>,
+    r_threshold: f32,
+    t_threshold: f32,
+    pixel_threshold: Option<f32>,
+}
+
+impl Setup {
+    pub(crate) fn new(r_threshold: f32, t_threshold: f32) -> Self {
+        Setup {
+            corres: vec![],
+            r_threshold,
+            t_threshold,
+            pixel_threshold: None,
+        }
+    }
+
+    pub(crate) fn pixel_threshold(&mut self) -> &mut Option<f32> {
+        &mut self.pixel_threshold
+    }
+
+    pub(crate) fn add_correspondence(
+        &mut self,
+        projected: ICoord,
+        world: WCoord,
+        k: &CameraK,
+        weight: f32,
+        covariance: &Mat2,
+    ) {
+        self.corres
+            .push(k.correspondence(projected, world, weight, covariance));
+    }
+
+    /// Threshold of the rotation constraints
+    pub(crate) fn rot_threshold(&self) -> Threshold {
+        match self.pixel_threshold {
+            Some(pixel) => Threshold::Pixel(pixel),
+            None => Threshold::Angle(self.r_threshold),
+        }
+    }
+
+    /// Threshold of the rays, selecting the translation and the inliers
+    pub(crate) fn ray_threshold(&self) -> Threshold {
+        match self.pixel_threshold {
+            Some(pixel) => Threshold::Pixel(pixel),
+            None => Threshold::Angle(self.t_threshold),
+        }
+    }
+}
+
+/// Decorator re-selecting the inliers at the refined pose and refining again, until the inliers
+/// stop changing
+///
+/// The inliers of the `Solution` then refer to `Solution::refined`. A failed solution is returned
+/// as is.
+pub struct Refined<E> {
+    estimator: E,
+    max_rounds: usize,
+}
+
+impl<E: PoseEstimator> Refined<E> {
+    pub fn new(estimator: E) -> Self {
+        Refined {
+            estimator,
+            max_rounds: 5,
+        }
+    }
+
+    pub fn max_rounds(&mut self) -> &mut usize {
+        &mut self.max_rounds
+    }
+
+    pub fn estimator(&mut self) -> &mut E {
+        &mut self.estimator
+    }
+
+    pub fn into_inner(self) -> E {
+        self.estimator
+    }
+}
+
+impl<E: PoseEstimator> PoseEstimator for Refined<E> {
+    fn reset_correspondence(&mut self) {
+        self.estimator.reset_correspondence();
+    }
+
+    fn reserve_correspondence(&mut self, n: usize) {
+        self.estimator.reserve_correspondence(n);
+    }
+
+    fn add_correspondence_with_covariance(
+        &mut self,
+        projected: ICoord,
+        world: WCoord,
+        k: &CameraK,
+        weight: f32,
+        covariance: Mat2,
+    ) {
+        self.estimator
+            .add_correspondence_with_covariance(projected, world, k, weight, covariance);
+    }
+
+    fn estimate(&self) -> Solution {
+        let mut solution = self.estimator.estimate();
+        if solution.failure.is_some() {
+            return solution;
+        }
+
+        let _span = span!("reselection");
+        for _ in 0..self.max_rounds {
+            if !self.reselect(&mut solution) {
+                break;
+            }
+            event!(
+                inliers = solution.inliers.iter().filter(|&&inlier| inlier).count(),
+                "reselected"
+            );
+        }
+
+        solution
+    }
+
+    fn reselect(&self, solution: &mut Solution) -> bool {
+        self.estimator.reselect(solution)
+    }
+}
@@ -0,0 +1,33 @@
+//! Opt-in instrumentation through `tracing`, compiled out without the `tracing` feature
+
+/// Enters a debug span, left when the returned guard is dropped
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($($arg:tt)*) => {
+        tracing::debug_span!($($arg)*).entered()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ($($arg:tt)*) => {
+        $crate::instrument::Entered
+    };
+}
+
+/// Emits a debug event in the current span
+#[cfg(feature = "tracing")]
+macro_rules! event {
+    ($($arg:tt)*) => {
+        tracing::debug!($($arg)*)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! event {
+    ($($arg:tt)*) => {};
+}
+
+/// Guard of a span compiled out
+#[cfg(not(feature = "tracing"))]
+pub struct Entered;
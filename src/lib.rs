@@ -1,33 +1,150 @@
 #![feature(binary_heap_retain)]
 
+#[macro_use]
+mod instrument;
+
 mod bnb;
 mod bounds;
 // mod bounds2;
 mod bounds3;
 mod corres;
+mod distortion;
+mod estimator;
+mod linalg;
+mod noise;
+mod p3p;
+mod pnp;
+mod ransac;
+mod refine;
 
+pub mod calibration;
+pub mod degeneracy;
+pub mod metrics;
+pub mod problem;
+pub mod reconstruction;
+#[cfg(feature = "synthetic")]
+pub mod synthetic;
+pub mod trace;
 pub mod types;
 
+pub use corres::Threshold;
+pub use distortion::Distortion;
+pub use estimator::{PoseEstimator, Refined};
+pub use ransac::RansacSolver;
+
 // use bounds::{RBAngleAxis, RBPolar};
 // use bounds2::{RBAngleAxis, RBPolarWithAngle};
 // use bounds2::{RBAngleAxis, RBPolar, RBPolarWithAngle};
-use bounds3::{RBAngleAxis, RBPolar};
-use corres::Corres;
-use glam::{Mat3A, Vec3A};
+use bounds3::{RBAngleAxis, RBAxis, RBPolar};
+use corres::{Constraint, Corres, CorresPair, LineCorres, VanishingCorres};
+use degeneracy::{Configuration, Degeneracy};
+use estimator::Setup;
+use glam::{Mat2, Mat3A, Quat, Vec3, Vec3A, Vec4};
+use noise::Spread;
 use std::f32::consts::{FRAC_PI_2, PI};
+use trace::Trace;
 use types::{CCoord, ICoord, WCoord};
 
 use crate::bounds::{RBound, RBounds};
 
 const F32_2PI: f32 = 2.0 * PI;
+const DEFAULT_TOLERANCE: f32 = 1e-2;
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RotationBound {
     AngleAxis,
     PolarCoordinate,
+    /// Searches yaw only, given the gravity direction in camera coordinates (e.g. from an IMU).
+    ///
+    /// The world frame is assumed to be gravity-aligned with gravity pointing along -Z.
+    Gravity(Vec3A),
+    /// Searches the rotation angle only, about a known rotation axis (e.g. planar motion).
+    Axis(Vec3A),
+    /// Solves the pose in closed form by EPnP on all correspondences, without a search.
+    ///
+    /// Assumes no outliers; needs at least 4 non-coplanar world points.
+    EPnP,
+    /// Solves the pose in closed form by the normalized DLT on all correspondences, without a
+    /// search.
+    ///
+    /// Assumes no outliers; needs at least 6 world points in general position, and fails with
+    /// `Failure::ClosedForm` on coplanar ones.
+    Dlt,
+    /// Solves the two poses of planar world points in closed form by IPPE on their homography,
+    /// without a search; the second is the `Solution::alternative`.
+    ///
+    /// Assumes no outliers; needs at least 4 coplanar world points, on the plane given by
+    /// `Solver::plane` or else detected, and fails with `Failure::NoPlane` otherwise.
+    Homography,
 }
 
+/// How a `RotationBound` finds the pose
 #[derive(Clone, Copy, Debug)]
+enum Method {
+    /// Searches the rotation by branch and bound, then the translation
+    Search(Domain),
+    ClosedForm(ClosedForm),
+}
+
+/// Rotations searched by branch and bound
+#[derive(Clone, Copy, Debug)]
+enum Domain {
+    AngleAxis,
+    PolarCoordinate,
+    Gravity(Vec3A),
+    Axis(Vec3A),
+}
+
+/// Closed-form solvers of the pose on all correspondences
+#[derive(Clone, Copy, Debug)]
+enum ClosedForm {
+    EPnP,
+    Dlt,
+    Homography,
+}
+
+impl RotationBound {
+    fn method(self) -> Method {
+        match self {
+            RotationBound::AngleAxis => Method::Search(Domain::AngleAxis),
+            RotationBound::PolarCoordinate => Method::Search(Domain::PolarCoordinate),
+            RotationBound::Gravity(gravity) => Method::Search(Domain::Gravity(gravity)),
+            RotationBound::Axis(axis) => Method::Search(Domain::Axis(axis)),
+            RotationBound::EPnP => Method::ClosedForm(ClosedForm::EPnP),
+            RotationBound::Dlt => Method::ClosedForm(ClosedForm::Dlt),
+            RotationBound::Homography => Method::ClosedForm(ClosedForm::Homography),
+        }
+    }
+}
+
+/// Objective maximized by the rotation search, as a function of each constraint's error
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Cost {
+    /// Counts the constraints whose error is below the threshold, each by its weight
+    #[default]
+    Inlier,
+    /// Scores `1 - error / threshold`, truncated at zero
+    TruncatedL1,
+    /// Scores `1 - (error / threshold)^2`, truncated at zero
+    TruncatedL2,
+}
+
+impl Cost {
+    /// Score of a single constraint; non-increasing in `error`
+    pub(crate) fn score(self, error: f32, threshold: f32) -> f32 {
+        match self {
+            Cost::Inlier if error < threshold => 1.0,
+            Cost::Inlier => 0.0,
+            Cost::TruncatedL1 => (1.0 - error / threshold).max(0.0),
+            Cost::TruncatedL2 => (1.0 - (error / threshold).powi(2)).max(0.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CameraK {
     pub fx: f32,
     pub fy: f32,
@@ -47,6 +164,70 @@ impl CameraK {
     pub fn to_camera_coord(&self, image: &ICoord) -> CCoord {
         (self.as_mat3a().inverse() * image.as_ivec2().as_vec2().extend(1.0)).into()
     }
+
+    /// Largest angular standard deviation of the ray through `camera`, given the pixel covariance
+    fn angular_noise(&self, camera: &CCoord, covariance: &Mat2) -> f32 {
+        // covariance on the normalized image plane
+        let (a, b, c) = (
+            covariance.x_axis.x / (self.fx * self.fx),
+            covariance.y_axis.x / (self.fx * self.fy),
+            covariance.y_axis.y / (self.fy * self.fy),
+        );
+        let max_eigen = (a + c) / 2.0 + (((a - c) / 2.0).powi(2) + b * b).sqrt();
+
+        // a displacement d on the plane z = 1 turns the ray by at most |d| / |ray|
+        max_eigen.sqrt() / camera.as_vec3a().length()
+    }
+
+    /// Builds the correspondence of an image point with the noise `covariance` in pixels²
+    fn correspondence(
+        &self,
+        projected: ICoord,
+        world: WCoord,
+        weight: f32,
+        covariance: &Mat2,
+    ) -> Corres {
+        let camera_coord = self.to_camera_coord(&projected);
+        let noise = self.angular_noise(&camera_coord, covariance);
+
+        Corres::new(camera_coord, world)
+            .with_weight(weight)
+            .with_noise(noise)
+    }
+
+    /// Builds the correspondence of an image line through two points with unit pixel noise
+    fn line_correspondence(
+        &self,
+        projected: [ICoord; 2],
+        world: [WCoord; 2],
+        weight: f32,
+    ) -> LineCorres {
+        let camera_coords = projected.map(|p| self.to_camera_coord(&p));
+        let noise = camera_coords
+            .iter()
+            .map(|c| self.angular_noise(c, &Mat2::IDENTITY))
+            .fold(0.0, f32::max);
+
+        LineCorres::new(camera_coords, world)
+            .with_weight(weight)
+            .with_noise(noise)
+    }
+
+    /// Builds the correspondence of a vanishing point in homogeneous pixel coordinates, whose
+    /// noise is taken as a unit pixel's at the principal point
+    fn vanishing_correspondence(
+        &self,
+        projected: Vec3A,
+        direction: Vec3A,
+        weight: f32,
+    ) -> VanishingCorres {
+        let camera_coord = CCoord::from(self.as_mat3a().inverse() * projected);
+        let noise = self.angular_noise(&Vec3A::Z.into(), &Mat2::IDENTITY);
+
+        VanishingCorres::new(camera_coord, direction)
+            .with_weight(weight)
+            .with_noise(noise)
+    }
 }
 
 impl From<Mat3A> for CameraK {
@@ -60,51 +241,864 @@ impl From<Mat3A> for CameraK {
     }
 }
 
+/// Rigid transformation from world to camera coordinates
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pose {
+    pub rotation: Mat3A,
+    pub translation: Vec3A,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Solution {
+    /// Pose whose rotation is certified optimal by the branch-and-bound search, or the closed-form
+    /// pose in the `EPnP` and `Dlt` modes
+    pub pose: Pose,
+    /// `pose` refined on the inliers by minimizing the reprojection error
+    pub refined: Pose,
+    /// Correspondences within the translation threshold of `pose`, in the order they were added
+    pub inliers: Vec<bool>,
+    /// Line correspondences within the translation threshold of `pose`, in the order they were
+    /// added
+    pub line_inliers: Vec<bool>,
+    /// Vanishing directions within the rotation threshold of `pose`, in the order they were added
+    pub vanishing_inliers: Vec<bool>,
+    /// Score of the rotation of `pose` under the solver's `Cost`, summed over every kind of
+    /// constraint
+    pub score: f32,
+    /// Threshold of the constraints the rotation was searched with, unless their kind sets its
+    /// own
+    pub rot_threshold: Threshold,
+    /// Threshold of the rays the translation and `inliers` were selected with
+    pub ray_threshold: Threshold,
+    /// Work done by the branch-and-bound search, zero when the pose was not searched
+    pub stats: SearchStats,
+    /// Degenerate pairs and configuration of the correspondences
+    pub degeneracy: Degeneracy,
+    /// Other pose of the two-fold ambiguity, when the world points are planar
+    pub alternative: Option<Alternative>,
+    /// Why no pose was determined, in which case the poses are the identity and nothing is an
+    /// inlier
+    pub failure: Option<Failure>,
+}
+
+/// Reason a `Solution` determines no pose
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Failure {
+    /// The configuration of the correspondences is degenerate, as `Solution::degeneracy` tells
+    Degenerate,
+    /// The closed-form solver found no pose, e.g. for too few points or points it cannot handle
+    ClosedForm,
+    /// The `Homography` mode was given no plane and detected none, or the world points are off
+    /// the given one
+    NoPlane,
+}
+
+impl Solution {
+    /// Identity pose without inliers, for correspondences which determine no pose
+    pub(crate) fn undetermined(
+        [n, lines, vanishing]: [usize; 3],
+        rot_threshold: Threshold,
+        ray_threshold: Threshold,
+        degeneracy: Degeneracy,
+        failure: Failure,
+    ) -> Self {
+        let pose = Pose {
+            rotation: Mat3A::IDENTITY,
+            translation: Vec3A::ZERO,
+        };
+
+        Solution {
+            pose,
+            refined: pose,
+            inliers: vec![false; n],
+            line_inliers: vec![false; lines],
+            vanishing_inliers: vec![false; vanishing],
+            score: 0.0,
+            rot_threshold,
+            ray_threshold,
+            stats: SearchStats::default(),
+            degeneracy,
+            alternative: None,
+            failure: Some(failure),
+        }
+    }
+}
+
+/// Pose of planar world points mirroring the solution's, which explains the image points about
+/// as well when the plane is seen under a narrow angle
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Alternative {
+    /// Solution's refined pose flipped about the line of sight to the plane, or the second
+    /// closed-form pose in the `Homography` mode, refined on the solution's inliers
+    pub pose: Pose,
+    /// Correspondences within the ray threshold of `pose`
+    pub inliers: Vec<bool>,
+    /// Line correspondences within the ray threshold of `pose`
+    pub line_inliers: Vec<bool>,
+    /// Score of the rotation of `pose` under the solver's `Cost`
+    pub score: f32,
+}
+
+impl Alternative {
+    /// Refines `start` on the inliers of `solution`, then selects and scores its own
+    pub(crate) fn new(
+        solution: &Solution,
+        corres: &[Corres],
+        lines: &[LineCorres],
+        vanishing: &[VanishingCorres],
+        constraints: &[Constraint],
+        start: Pose,
+        cost: Cost,
+    ) -> Self {
+        let pose = refine::refine_mixed(
+            corres,
+            &solution.inliers,
+            lines,
+            &solution.line_inliers,
+            vanishing,
+            &solution.vanishing_inliers,
+            start,
+        );
+
+        Alternative {
+            inliers: refine::ray_inliers(corres, &pose, solution.ray_threshold),
+            line_inliers: refine::line_inliers(lines, &pose, solution.ray_threshold),
+            score: Constraint::score(constraints, &pose.rotation, solution.rot_threshold, cost),
+            pose,
+        }
+    }
+}
+
+/// Work done by the branch-and-bound search
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchStats {
+    /// Branches subdivided
+    pub expanded: usize,
+    /// Largest number of branches queued at once
+    pub max_queue: usize,
+}
+
+/// Weight and threshold of one kind of constraint in the rotation search
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KindSettings {
+    /// Factor of the weights of the constraints, and of the correspondences' in the translation,
+    /// the support and the refinement
+    pub weight: f32,
+    /// Value replacing the rotation threshold's, in its unit, for the constraints; it is then
+    /// kept as is by `Solver::auto_threshold`
+    pub threshold: Option<f32>,
+}
+
+impl Default for KindSettings {
+    fn default() -> Self {
+        KindSettings {
+            weight: 1.0,
+            threshold: None,
+        }
+    }
+}
+
+/// Settings of each kind of constraint summed by the rotation search
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConstraintSettings {
+    /// Constraints of the pairs of point correspondences
+    pub pairs: KindSettings,
+    /// Constraints of the line correspondences
+    pub lines: KindSettings,
+    /// Constraints of the vanishing directions
+    pub vanishing: KindSettings,
+}
+
+/// Pose and inliers found at the given thresholds
+struct Search {
+    pose: Pose,
+    inliers: Vec<bool>,
+    line_inliers: Vec<bool>,
+    rot_threshold: Threshold,
+    ray_threshold: Threshold,
+    stats: SearchStats,
+    /// Second closed-form pose, in the `Homography` mode
+    alternative: Option<Pose>,
+}
+
+/// Correspondences and configuration of a `Solver`, to record a solve and replay it
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    setup: Setup,
+    lines: Vec<LineCorres>,
+    vanishing: Vec<VanishingCorres>,
+    constraint_settings: ConstraintSettings,
+    auto_threshold: bool,
+    rot_bound: RotationBound,
+    cost: Cost,
+    tolerance: f32,
+    hint: Option<Mat3A>,
+    plane: Option<Vec3A>,
+}
+
 pub struct Solver {
-    corres: Vec<Corres>,
-    r_threshold: f32,
-    #[allow(dead_code)]
-    t_threshold: f32,
+    setup: Setup,
+    lines: Vec<LineCorres>,
+    vanishing: Vec<VanishingCorres>,
+    constraint_settings: ConstraintSettings,
+    auto_threshold: bool,
     rot_bound: RotationBound,
+    cost: Cost,
+    tolerance: f32,
+    hint: Option<Mat3A>,
+    plane: Option<Vec3A>,
 }
 
 impl Solver {
     pub fn new(r_threshold: f32, t_threshold: f32) -> Self {
         Solver {
-            corres: vec![],
-            r_threshold,
-            t_threshold,
+            setup: Setup::new(r_threshold, t_threshold),
+            lines: vec![],
+            vanishing: vec![],
+            constraint_settings: ConstraintSettings::default(),
+            auto_threshold: false,
             rot_bound: RotationBound::AngleAxis,
+            cost: Cost::default(),
+            tolerance: DEFAULT_TOLERANCE,
+            hint: None,
+            plane: None,
         }
     }
 
+    /// Inlier threshold in pixels, replacing `r_threshold` and `t_threshold` when set.
+    ///
+    /// The angular thresholds are derived per correspondence from its pixel covariance and the
+    /// camera, and per pair from the angle between its rays.
+    pub fn pixel_threshold(&mut self) -> &mut Option<f32> {
+        self.setup.pixel_threshold()
+    }
+
+    /// Estimates the thresholds from the noise of the data when enabled.
+    ///
+    /// The configured thresholds then act as coarse upper limits: the search runs at them first,
+    /// and again at the thresholds fitted to the residuals of its inliers, which are kept above a
+    /// tenth of them.
+    pub fn auto_threshold(&mut self) -> &mut bool {
+        &mut self.auto_threshold
+    }
+
     pub fn rot_bound(&mut self) -> &mut RotationBound {
         &mut self.rot_bound
     }
 
+    pub fn cost(&mut self) -> &mut Cost {
+        &mut self.cost
+    }
+
+    /// Score gap, relative to the best score, below which the search stops; the result is optimal
+    /// up to this share of its score
+    pub fn tolerance(&mut self) -> &mut f32 {
+        &mut self.tolerance
+    }
+
+    /// Rotation expected to be close to the solution (e.g. the previous frame's pose).
+    ///
+    /// It is evaluated before the search to seed the lower bound, so the result stays globally
+    /// optimal while far branches are pruned early. `RansacSolver` provides a cheap one.
+    ///
+    /// Only the lower bound is seeded: the branches are still expanded in the order of their
+    /// upper bounds, not of their distance to the hint.
+    pub fn hint(&mut self) -> &mut Option<Mat3A> {
+        &mut self.hint
+    }
+
+    /// Normal of the plane of the world points, in world coordinates; detected when unset.
+    ///
+    /// Given a plane, the solution carries the other pose of the two-fold ambiguity.
+    pub fn plane(&mut self) -> &mut Option<Vec3A> {
+        &mut self.plane
+    }
+
+    /// Adds the correspondence of the image line through `projected` with the world line through
+    /// `world`, counted in proportion to `weight`
+    ///
+    /// The points need not correspond one to one, e.g. the ends of a detected segment and of a
+    /// mapped edge. Its direction constrains the rotation along with the correspondence pairs, and
+    /// `Solution::line_inliers` lists the lines agreeing with the pose.
+    ///
+    /// Lines of a few directions only, e.g. the three of a room, also satisfy rotations permuting
+    /// these directions; points or lines of other directions tell them apart.
+    pub fn add_line_correspondence(
+        &mut self,
+        projected: [ICoord; 2],
+        world: [WCoord; 2],
+        k: &CameraK,
+        weight: f32,
+    ) {
+        self.lines
+            .push(k.line_correspondence(projected, world, weight));
+    }
+
+    /// Adds the correspondence of a vanishing point with the world direction of the lines meeting
+    /// there, counted in proportion to `weight`
+    ///
+    /// The vanishing point is in homogeneous pixel coordinates, `(u, v, 1)` or `(du, dv, 0)` at
+    /// infinity. It constrains the rotation only, along with the pairs and lines.
+    pub fn add_vanishing_direction(
+        &mut self,
+        projected: Vec3A,
+        direction: Vec3A,
+        k: &CameraK,
+        weight: f32,
+    ) {
+        self.vanishing
+            .push(k.vanishing_correspondence(projected, direction, weight));
+    }
+
+    /// Weights and thresholds of the kinds of constraints, e.g. to trust lines over points in
+    /// scenes of little texture
+    pub fn constraint_settings(&mut self) -> &mut ConstraintSettings {
+        &mut self.constraint_settings
+    }
+
     pub fn reset_correspondence(&mut self) {
-        self.corres.clear();
+        self.setup.corres.clear();
+        self.lines.clear();
+        self.vanishing.clear();
     }
 
     pub fn reserve_correspondence(&mut self, n: usize) {
-        self.corres.reserve(n);
+        self.setup.corres.reserve(n);
     }
 
     pub fn add_correspondence(&mut self, projected: ICoord, world: WCoord, k: &CameraK) {
-        let camera_coord = k.to_camera_coord(&projected);
+        self.add_weighted_correspondence(projected, world, k, 1.0);
+    }
+
+    /// Adds a correspondence counted in proportion to `weight`
+    pub fn add_weighted_correspondence(
+        &mut self,
+        projected: ICoord,
+        world: WCoord,
+        k: &CameraK,
+        weight: f32,
+    ) {
+        self.add_correspondence_with_covariance(projected, world, k, weight, Mat2::IDENTITY);
+    }
+
+    /// Adds a correspondence whose image point has the noise `covariance` in pixels²
+    pub fn add_correspondence_with_covariance(
+        &mut self,
+        projected: ICoord,
+        world: WCoord,
+        k: &CameraK,
+        weight: f32,
+        covariance: Mat2,
+    ) {
+        self.setup
+            .add_correspondence(projected, world, k, weight, &covariance);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            setup: self.setup.clone(),
+            lines: self.lines.clone(),
+            vanishing: self.vanishing.clone(),
+            constraint_settings: self.constraint_settings,
+            auto_threshold: self.auto_threshold,
+            rot_bound: self.rot_bound,
+            cost: self.cost,
+            tolerance: self.tolerance,
+            hint: self.hint,
+            plane: self.plane,
+        }
+    }
 
-        self.corres.push(Corres::new(camera_coord, world));
+    /// Restores a solver which solves exactly as the one the snapshot was taken of
+    pub fn from_snapshot(snapshot: Snapshot) -> Self {
+        Solver {
+            setup: snapshot.setup,
+            lines: snapshot.lines,
+            vanishing: snapshot.vanishing,
+            constraint_settings: snapshot.constraint_settings,
+            auto_threshold: snapshot.auto_threshold,
+            rot_bound: snapshot.rot_bound,
+            cost: snapshot.cost,
+            tolerance: snapshot.tolerance,
+            hint: snapshot.hint,
+            plane: snapshot.plane,
+        }
     }
 
+    /// Searches the pose as `solve` without refining it, returning the identity when `solve` finds
+    /// none
     pub fn pose(&self) -> (Mat3A, Vec3A) {
-        #[cfg(debug_assertions)]
-        match self.rot_bound {
-            RotationBound::AngleAxis => println!("Mode: angle-axis based"),
-            RotationBound::PolarCoordinate => println!("Mode: polar based"),
+        let _span = span!("pose", mode = ?self.rot_bound);
+        match self.certified(&self.degeneracy(), None) {
+            Ok(Search { pose, .. }) => (pose.rotation, pose.translation),
+            Err(_) => (Mat3A::IDENTITY, Vec3A::ZERO),
         }
+    }
+
+    /// Solves the pose and refines it on the inliers of the certified solution
+    pub fn solve(&self) -> Solution {
+        let _span = span!("solve", mode = ?self.rot_bound);
+        self.solution(None)
+    }
+
+    /// Solves as `solve`, recording the branches expanded and pruned by the search
+    pub fn solve_traced(&self) -> (Solution, Trace) {
+        let _span = span!("solve", mode = ?self.rot_bound);
+        let mut trace = Trace::new();
+        let solution = self.solution(Some(&mut trace));
 
-        let rot = match self.rot_bound {
-            RotationBound::AngleAxis => {
+        (solution, trace)
+    }
+
+    /// Checks the configuration, then searches the pose, refines it and scores it
+    fn solution(&self, trace: Option<&mut Trace>) -> Solution {
+        let degeneracy = self.degeneracy();
+        let Search {
+            pose,
+            inliers,
+            line_inliers,
+            rot_threshold,
+            ray_threshold,
+            stats,
+            alternative,
+        } = match self.certified(&degeneracy, trace) {
+            Ok(search) => search,
+            Err(failure) => {
+                return Solution::undetermined(
+                    [
+                        self.setup.corres.len(),
+                        self.lines.len(),
+                        self.vanishing.len(),
+                    ],
+                    self.setup.rot_threshold(),
+                    self.setup.ray_threshold(),
+                    degeneracy,
+                    failure,
+                )
+            }
+        };
+        let plane = self.plane.or(degeneracy.plane_normal());
+        let constraints = self.constraints();
+        let [_, _, vanishing_constraints] = self.split(&constraints);
+        let vanishing_inliers =
+            refine::satisfied(vanishing_constraints, &pose.rotation, rot_threshold);
+        let (corres, lines, vanishing) = self.weighted();
+        let refined = {
+            let _span = span!("refinement");
+            let refined = refine::refine_mixed(
+                &corres,
+                &inliers,
+                &lines,
+                &line_inliers,
+                &vanishing,
+                &vanishing_inliers,
+                pose,
+            );
+            event!(
+                inliers = inliers.iter().filter(|&&inlier| inlier).count(),
+                line_inliers = line_inliers.iter().filter(|&&inlier| inlier).count(),
+                "refined"
+            );
+
+            refined
+        };
+        let score = Constraint::score(&constraints, &pose.rotation, rot_threshold, self.cost);
+
+        let mut solution = Solution {
+            pose,
+            refined,
+            inliers,
+            line_inliers,
+            vanishing_inliers,
+            score,
+            rot_threshold,
+            ray_threshold,
+            stats,
+            degeneracy,
+            alternative: None,
+            failure: None,
+        };
+        solution.alternative = plane.map(|normal| {
+            let _span = span!("alternative");
+            let start = alternative.unwrap_or_else(|| {
+                pnp::flip(
+                    &solution.refined,
+                    normal,
+                    &self.setup.corres,
+                    &solution.inliers,
+                )
+            });
+            let alternative = Alternative::new(
+                &solution,
+                &corres,
+                &lines,
+                &vanishing,
+                &constraints,
+                start,
+                self.cost,
+            );
+            event!(
+                inliers = alternative.inliers.iter().filter(|&&inlier| inlier).count(),
+                score = alternative.score,
+                "refined"
+            );
+
+            alternative
+        });
+
+        solution
+    }
+
+    /// Checks the configuration of the correspondences
+    fn degeneracy(&self) -> Degeneracy {
+        let _span = span!("degeneracy_check");
+        let degeneracy = Degeneracy::check(&self.setup.corres, &self.lines, &self.vanishing);
+        event!(
+            pairs = degeneracy.pairs.len(),
+            lines = degeneracy.lines.len(),
+            configuration = ?degeneracy.configuration,
+            "checked"
+        );
+
+        degeneracy
+    }
+
+    /// Searches the pose, unless the configuration determines none
+    fn certified(
+        &self,
+        degeneracy: &Degeneracy,
+        trace: Option<&mut Trace>,
+    ) -> Result<Search, Failure> {
+        if degeneracy.is_fatal() {
+            return Err(Failure::Degenerate);
+        }
+
+        let plane = self.plane.or(degeneracy.plane_normal());
+        let turns = match self.plane {
+            Some(normal) => vec![normal],
+            None => degeneracy.turns(&self.vanishing),
+        };
+        let search = self.estimate_search(plane, &turns, trace)?;
+        // only the points decide between the turns of a fixed line
+        let fixed_line = matches!(
+            degeneracy.configuration,
+            Some(Configuration::FixedLine { .. })
+        );
+        if fixed_line && !search.inliers.contains(&true) {
+            return Err(Failure::Degenerate);
+        }
+
+        Ok(search)
+    }
+
+    /// Searches at the configured thresholds, then at the estimated ones if enabled
+    fn estimate_search(
+        &self,
+        plane: Option<Vec3A>,
+        turns: &[Vec3A],
+        mut trace: Option<&mut Trace>,
+    ) -> Result<Search, Failure> {
+        let (rot_threshold, ray_threshold) = {
+            let _span = span!("setup");
+            let thresholds = (self.setup.rot_threshold(), self.setup.ray_threshold());
+            event!(
+                correspondences = self.setup.corres.len(),
+                rot_threshold = ?thresholds.0,
+                ray_threshold = ?thresholds.1,
+                auto_threshold = self.auto_threshold,
+                hint = self.hint.is_some(),
+                "configured"
+            );
+
+            thresholds
+        };
+        let coarse = self.search(
+            rot_threshold,
+            ray_threshold,
+            self.hint,
+            plane,
+            turns,
+            trace.as_deref_mut(),
+        )?;
+        if !self.auto_threshold {
+            return Ok(coarse);
+        }
+
+        let constraints = self.constraints();
+        let [pair_constraints, line_constraints, vanishing_constraints] = self.split(&constraints);
+        let pair_inliers = (0..pair_constraints.len())
+            .map(|i| coarse.inliers[2 * i] && coarse.inliers[2 * i + 1])
+            .collect::<Vec<_>>();
+        let vanishing_inliers = refine::satisfied(
+            vanishing_constraints,
+            &coarse.pose.rotation,
+            coarse.rot_threshold,
+        );
+        // residuals at the certified pose are inflated by the coarse thresholds' slack
+        let (corres, lines, vanishing) = self.weighted();
+        let Pose {
+            rotation,
+            translation,
+        } = refine::refine_mixed(
+            &corres,
+            &coarse.inliers,
+            &lines,
+            &coarse.line_inliers,
+            &vanishing,
+            &vanishing_inliers,
+            coarse.pose,
+        );
+        let settings = self.constraint_settings;
+        // planes tilt about a single axis, while vanishing rays deviate in two directions
+        let pair_residuals = [
+            (
+                pair_constraints,
+                &pair_inliers,
+                settings.pairs,
+                Spread::HalfNormal,
+            ),
+            (
+                line_constraints,
+                &coarse.line_inliers,
+                settings.lines,
+                Spread::HalfNormal,
+            ),
+            (
+                vanishing_constraints,
+                &vanishing_inliers,
+                settings.vanishing,
+                Spread::Rayleigh,
+            ),
+        ]
+        .into_iter()
+        // kinds with their own threshold keep it, so their residuals would only skew the others'
+        .filter(|(_, _, kind, _)| kind.threshold.is_none())
+        .flat_map(|(constraints, inliers, _, spread)| {
+            constraints
+                .iter()
+                .zip(inliers)
+                .filter(|(_, &inlier)| inlier)
+                .map(move |(constraint, _)| (constraint, spread))
+        })
+        .map(|(constraint, spread)| {
+            let unit = constraint.threshold(coarse.rot_threshold) / coarse.rot_threshold.value();
+
+            spread.normalize(constraint.error(&rotation) / unit)
+        })
+        .collect();
+        let unit = |threshold: f32| threshold / coarse.ray_threshold.value();
+        let point_residuals = self
+            .setup
+            .corres
+            .iter()
+            .zip(&coarse.inliers)
+            .filter(|(_, &inlier)| inlier)
+            .map(|(c, _)| {
+                Spread::Rayleigh.normalize(
+                    refine::ray_error(c, &rotation, translation)
+                        / unit(c.threshold(coarse.ray_threshold)),
+                )
+            });
+        let line_residuals = self
+            .lines
+            .iter()
+            .zip(&coarse.line_inliers)
+            .filter(|(_, &inlier)| inlier)
+            .map(|(line, _)| {
+                Spread::HalfNormal.normalize(
+                    line.error(&rotation, translation) / unit(line.threshold(coarse.ray_threshold)),
+                )
+            });
+        let ray_residuals = point_residuals.chain(line_residuals).collect();
+
+        let fitted = |threshold: Threshold, residuals| {
+            noise::threshold(residuals, threshold.value())
+                .map(|value| threshold.with_value(value))
+                .unwrap_or(threshold)
+        };
+        let rot_threshold = fitted(coarse.rot_threshold, pair_residuals);
+        let ray_threshold = fitted(coarse.ray_threshold, ray_residuals);
+        event!(rot_threshold = ?rot_threshold, ray_threshold = ?ray_threshold, "fitted thresholds");
+
+        // the refined solution is close, so it makes the second search cheap
+        let mut fine = self.search(
+            rot_threshold,
+            ray_threshold,
+            Some(rotation),
+            plane,
+            turns,
+            trace,
+        )?;
+        fine.stats.expanded += coarse.stats.expanded;
+        fine.stats.max_queue = fine.stats.max_queue.max(coarse.stats.max_queue);
+
+        Ok(fine)
+    }
+
+    /// Searches the rotation, then estimates the translation on its inliers
+    ///
+    /// The constraints hold about equally for the rotation turned by π about each of the `turns`,
+    /// e.g. the normal of planar world points, so all are tried and the best scoring is kept, the
+    /// support deciding between scores within the tolerance. The domains of a prior keep the
+    /// searched rotation, which the turns would leave.
+    fn search(
+        &self,
+        rot_threshold: Threshold,
+        ray_threshold: Threshold,
+        hint: Option<Mat3A>,
+        plane: Option<Vec3A>,
+        turns: &[Vec3A],
+        trace: Option<&mut Trace>,
+    ) -> Result<Search, Failure> {
+        let domain = match self.rot_bound.method() {
+            Method::Search(domain) => domain,
+            Method::ClosedForm(method) => {
+                return self.closed_form(method, rot_threshold, ray_threshold, plane)
+            }
+        };
+        // a turned rotation generally leaves the gravity direction or the axis of the prior
+        let turns = match domain {
+            Domain::AngleAxis | Domain::PolarCoordinate => turns,
+            Domain::Gravity(_) | Domain::Axis(_) => &[],
+        };
+
+        let constraints = self.constraints();
+        let [pair_constraints, line_constraints, _] = self.split(&constraints);
+        let mut stats = SearchStats::default();
+        let rotation = {
+            let _span = span!("rotation_search", threshold = ?rot_threshold);
+            let rotation =
+                self.rotation(domain, rot_threshold, hint, &constraints, &mut stats, trace);
+            event!(
+                expanded = stats.expanded,
+                max_queue = stats.max_queue,
+                "searched"
+            );
+
+            rotation
+        };
+        let (rotation, translation, inliers, line_inliers) = {
+            let _span = span!("translation_search", threshold = ?ray_threshold);
+            let pair_inliers = refine::inliers(
+                &self.setup.corres,
+                pair_constraints,
+                &rotation,
+                rot_threshold,
+            );
+            let (corres, lines, _) = self.weighted();
+            let turned = turns
+                .iter()
+                .map(|&axis| rotation * Mat3A::from_axis_angle(axis.into(), PI));
+            let (rotation, translation, inliers, line_inliers) = std::iter::once(rotation)
+                .chain(turned)
+                .map(|rotation| {
+                    let direction_inliers =
+                        refine::satisfied(line_constraints, &rotation, rot_threshold);
+                    let (translation, inliers, line_inliers) = refine::translation(
+                        &corres,
+                        &lines,
+                        &pair_inliers,
+                        &direction_inliers,
+                        &rotation,
+                        ray_threshold,
+                    );
+                    (rotation, translation, inliers, line_inliers)
+                })
+                // the turns only tie some constraints, so the score decides unless it is within
+                // the tolerance, where the support breaks the tie
+                .max_by(|(a_rot, _, a, a_lines), (b_rot, _, b, b_lines)| {
+                    let score = |rotation| {
+                        Constraint::score(&constraints, rotation, rot_threshold, self.cost)
+                    };
+                    let support = |inliers, line_inliers| {
+                        refine::support(&corres, inliers)
+                            + refine::line_support(&lines, line_inliers)
+                    };
+                    let (a_score, b_score) = (score(a_rot), score(b_rot));
+                    if (a_score - b_score).abs() > self.tolerance * a_score.max(b_score) {
+                        a_score.total_cmp(&b_score)
+                    } else {
+                        support(a, a_lines).total_cmp(&support(b, b_lines))
+                    }
+                })
+                .expect("at least the searched rotation is a candidate");
+            event!(
+                inliers = inliers.iter().filter(|&&inlier| inlier).count(),
+                line_inliers = line_inliers.iter().filter(|&&inlier| inlier).count(),
+                "searched"
+            );
+
+            (rotation, translation, inliers, line_inliers)
+        };
+
+        Ok(Search {
+            pose: Pose {
+                rotation,
+                translation,
+            },
+            inliers,
+            line_inliers,
+            rot_threshold,
+            ray_threshold,
+            stats,
+            alternative: None,
+        })
+    }
+
+    /// Solves the pose in closed form on all correspondences, then selects its inliers
+    fn closed_form(
+        &self,
+        method: ClosedForm,
+        rot_threshold: Threshold,
+        ray_threshold: Threshold,
+        plane: Option<Vec3A>,
+    ) -> Result<Search, Failure> {
+        let _span = span!("closed_form");
+        let corres = &self.setup.corres;
+        let poses = match method {
+            ClosedForm::EPnP => pnp::epnp(corres).map(|pose| (pose, None)),
+            ClosedForm::Dlt => pnp::dlt(corres).map(|pose| (pose, None)),
+            ClosedForm::Homography => {
+                let normal = plane
+                    .filter(|&normal| degeneracy::on_plane(corres, normal))
+                    .ok_or(Failure::NoPlane)?;
+                pnp::ippe(corres, normal).map(|[pose, alternative]| (pose, Some(alternative)))
+            }
+        };
+        event!(solved = poses.is_some(), "solved");
+        let (pose, alternative) = poses.ok_or(Failure::ClosedForm)?;
+
+        Ok(Search {
+            inliers: refine::ray_inliers(corres, &pose, ray_threshold),
+            line_inliers: refine::line_inliers(&self.lines, &pose, ray_threshold),
+            pose,
+            rot_threshold,
+            ray_threshold,
+            stats: SearchStats::default(),
+            alternative,
+        })
+    }
+
+    fn rotation(
+        &self,
+        domain: Domain,
+        threshold: Threshold,
+        hint: Option<Mat3A>,
+        constraints: &[Constraint],
+        stats: &mut SearchStats,
+        trace: Option<&mut Trace>,
+    ) -> Mat3A {
+        match domain {
+            Domain::AngleAxis => {
                 // bnb::bnb_rot(
                 //     RBAngleAxis::new(Vec3A::ZERO, F32_2PI),
                 //     &self.corres,
@@ -116,11 +1110,16 @@ impl Solver {
                 //     self.r_threshold,
                 // )
                 bnb::bnb_rot3(
-                    vec![RBAngleAxis::new(Vec3A::ZERO, F32_2PI, &self.corres)],
-                    self.r_threshold,
+                    vec![RBAngleAxis::new(Vec3A::ZERO, F32_2PI, constraints)],
+                    threshold,
+                    self.cost,
+                    self.tolerance,
+                    self.incumbent(hint, threshold, constraints),
+                    stats,
+                    trace,
                 )
             }
-            RotationBound::PolarCoordinate => {
+            Domain::PolarCoordinate => {
                 // bnb::bnb_rot(RBPolar::default(), &self.corres, self.r_threshold)
                 // bnb::bnb_rot2(
                 //     vec![RBPolarWithAngle::new(
@@ -168,16 +1167,193 @@ impl Solver {
                         -PI..=PI,
                         -FRAC_PI_2..=FRAC_PI_2,
                         -PI..=PI,
-                        &self.corres,
+                        constraints,
                     )],
-                    self.r_threshold,
+                    threshold,
+                    self.cost,
+                    self.tolerance,
+                    self.incumbent(hint, threshold, constraints),
+                    stats,
+                    trace,
                 )
             }
-        };
+            Domain::Gravity(gravity) => {
+                // world +Z (up) has to be mapped onto the measured up direction,
+                // so the remaining freedom is the yaw about that direction.
+                let up = -gravity.normalize();
+                let align = Mat3A::from_quat(Quat::from_rotation_arc(Vec3::Z, up.into()));
+                let hint = hint.map(|hint| twist(hint * align.transpose(), up) * align);
+
+                bnb::bnb_rot3(
+                    vec![RBAxis::new(up, align, -PI..=PI, constraints)],
+                    threshold,
+                    self.cost,
+                    self.tolerance,
+                    self.incumbent(hint, threshold, constraints),
+                    stats,
+                    trace,
+                )
+            }
+            Domain::Axis(axis) => {
+                let hint = hint.map(|hint| twist(hint, axis));
+
+                bnb::bnb_rot3(
+                    vec![RBAxis::new(axis, Mat3A::IDENTITY, -PI..=PI, constraints)],
+                    threshold,
+                    self.cost,
+                    self.tolerance,
+                    self.incumbent(hint, threshold, constraints),
+                    stats,
+                    trace,
+                )
+            }
+        }
+    }
+
+    /// Rotation constraints of the correspondence pairs, then of the lines and of the vanishing
+    /// directions, with the settings of their kinds
+    fn constraints(&self) -> Vec<Constraint> {
+        let settings = self.constraint_settings;
+        let pairs = CorresPair::make_pairs(&self.setup.corres);
+        let pairs = pairs
+            .iter()
+            .map(|c_pair| c_pair.constraint().with_settings(settings.pairs));
+        let lines = self
+            .lines
+            .iter()
+            .map(|line| line.constraint().with_settings(settings.lines));
+        let vanishing = self
+            .vanishing
+            .iter()
+            .map(|vanishing| vanishing.constraint().with_settings(settings.vanishing));
+
+        pairs.chain(lines).chain(vanishing).collect()
+    }
+
+    /// Re-selects the inliers at the refined pose of `solution` and refines it on them with the
+    /// lines and vanishing directions, returning whether they changed
+    fn reselect(&self, solution: &mut Solution) -> bool {
+        let pose = solution.refined;
+        let inliers = refine::ray_inliers(&self.setup.corres, &pose, solution.ray_threshold);
+        let line_inliers = refine::line_inliers(&self.lines, &pose, solution.ray_threshold);
+        let constraints = self.constraints();
+        let [_, _, vanishing_constraints] = self.split(&constraints);
+        let vanishing_inliers = refine::satisfied(
+            vanishing_constraints,
+            &pose.rotation,
+            solution.rot_threshold,
+        );
+        if inliers == solution.inliers
+            && line_inliers == solution.line_inliers
+            && vanishing_inliers == solution.vanishing_inliers
+        {
+            return false;
+        }
+
+        let (corres, lines, vanishing) = self.weighted();
+        solution.refined = refine::refine_mixed(
+            &corres,
+            &inliers,
+            &lines,
+            &line_inliers,
+            &vanishing,
+            &vanishing_inliers,
+            pose,
+        );
+        solution.inliers = inliers;
+        solution.line_inliers = line_inliers;
+        solution.vanishing_inliers = vanishing_inliers;
+
+        true
+    }
+
+    /// Copies of the correspondences, lines and vanishing directions with their weights scaled
+    /// by those of their kinds, for the translation, the support and the refinement
+    fn weighted(&self) -> (Vec<Corres>, Vec<LineCorres>, Vec<VanishingCorres>) {
+        let settings = self.constraint_settings;
+        let corres = self
+            .setup
+            .corres
+            .iter()
+            .map(|c| c.with_weight(c.weight() * settings.pairs.weight))
+            .collect();
+        let lines = self
+            .lines
+            .iter()
+            .map(|line| line.with_weight(line.weight() * settings.lines.weight))
+            .collect();
+        let vanishing = self
+            .vanishing
+            .iter()
+            .map(|vanishing| vanishing.with_weight(vanishing.weight() * settings.vanishing.weight))
+            .collect();
+
+        (corres, lines, vanishing)
+    }
+
+    /// Splits the `constraints` into those of the pairs, of the lines and of the vanishing
+    /// directions
+    fn split<'a>(&self, constraints: &'a [Constraint]) -> [&'a [Constraint]; 3] {
+        let (pairs, rest) = constraints.split_at(self.setup.corres.len() / 2);
+        let (lines, vanishing) = rest.split_at(self.lines.len());
+
+        [pairs, lines, vanishing]
+    }
+
+    /// Scores a rotation to seed the search with
+    fn incumbent(
+        &self,
+        rot: Option<Mat3A>,
+        threshold: Threshold,
+        constraints: &[Constraint],
+    ) -> Option<(f32, Mat3A)> {
+        let rot = rot?;
+
+        Some((
+            Constraint::score(constraints, &rot, threshold, self.cost),
+            rot,
+        ))
+    }
+}
+
+impl PoseEstimator for Solver {
+    fn reset_correspondence(&mut self) {
+        Solver::reset_correspondence(self);
+    }
+
+    fn reserve_correspondence(&mut self, n: usize) {
+        Solver::reserve_correspondence(self, n);
+    }
+
+    fn add_correspondence_with_covariance(
+        &mut self,
+        projected: ICoord,
+        world: WCoord,
+        k: &CameraK,
+        weight: f32,
+        covariance: Mat2,
+    ) {
+        Solver::add_correspondence_with_covariance(self, projected, world, k, weight, covariance);
+    }
+
+    fn estimate(&self) -> Solution {
+        self.solve()
+    }
+
+    fn reselect(&self, solution: &mut Solution) -> bool {
+        Solver::reselect(self, solution)
+    }
+}
 
-        #[cfg(debug_assertions)]
-        println!("to estimate of translation has not been implemented yet...");
+/// Extracts the rotation component of `rot` about `axis` (swing-twist decomposition)
+fn twist(rot: Mat3A, axis: Vec3A) -> Mat3A {
+    let quat = Quat::from_mat3a(&rot);
+    let axis = axis.normalize();
+    let projected = axis * axis.dot(quat.xyz().into());
+    let twist = Vec4::from((projected, quat.w));
 
-        (rot, Vec3A::ZERO)
+    match twist.try_normalize() {
+        Some(twist) => Mat3A::from_quat(Quat::from_vec4(twist)),
+        None => Mat3A::IDENTITY,
     }
 }
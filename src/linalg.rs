@@ -0,0 +1,162 @@
+use glam::{DMat3, DQuat};
+
+/// Solves `a * x = b` by Gaussian elimination with partial pivoting
+pub fn solve<const N: usize>(mut a: [[f64; N]; N], mut b: [f64; N]) -> Option<[f64; N]> {
+    for col in 0..N {
+        let pivot = (col..N).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < f64::EPSILON {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let pivot_row = a[col];
+        for row in (col + 1)..N {
+            let factor = a[row][col] / pivot_row[col];
+            for (x, p) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= factor * p;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    let mut x = [0.0; N];
+    for row in (0..N).rev() {
+        let sum: f64 = ((row + 1)..N).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+
+    Some(x)
+}
+
+/// Eigen-decomposes the symmetric matrix `a` by cyclic Jacobi rotations
+///
+/// Returns the eigenvalues in ascending order, and the corresponding unit eigenvectors as rows.
+pub fn sym_eigen<const N: usize>(mut a: [[f64; N]; N]) -> ([f64; N], [[f64; N]; N]) {
+    const SWEEPS: usize = 64;
+
+    let mut v = [[0.0; N]; N];
+    for (i, row) in v.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+
+    for _ in 0..SWEEPS {
+        let off: f64 = (0..N)
+            .flat_map(|p| ((p + 1)..N).map(move |q| (p, q)))
+            .map(|(p, q)| a[p][q] * a[p][q])
+            .sum();
+        if off < 1e-30 {
+            break;
+        }
+
+        for p in 0..N {
+            for q in (p + 1)..N {
+                if a[p][q].abs() < 1e-300 {
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+
+                for row in a.iter_mut() {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (ap, aq) = (a[p], a[q]);
+                a[p] = std::array::from_fn(|k| c * ap[k] - s * aq[k]);
+                a[q] = std::array::from_fn(|k| s * ap[k] + c * aq[k]);
+                for row in v.iter_mut() {
+                    let (vp, vq) = (row[p], row[q]);
+                    row[p] = c * vp - s * vq;
+                    row[q] = s * vp + c * vq;
+                }
+            }
+        }
+    }
+
+    let mut order: [usize; N] = std::array::from_fn(|i| i);
+    order.sort_by(|&i, &j| a[i][i].total_cmp(&a[j][j]));
+
+    (
+        order.map(|i| a[i][i]),
+        order.map(|i| std::array::from_fn(|k| v[k][i])),
+    )
+}
+
+/// Finds the real roots of `c[0] + c[1] x + ... + c[N - 1] x^(N - 1)` (Durand-Kerner)
+pub fn real_roots<const N: usize>(c: [f64; N]) -> Vec<f64> {
+    const ITERATIONS: usize = 500;
+
+    let degree = (0..N)
+        .rev()
+        .find(|&i| c[i].abs() > f64::EPSILON)
+        .unwrap_or(0);
+    if degree == 0 {
+        return vec![];
+    }
+    let monic: Vec<f64> = c[..=degree].iter().map(|v| v / c[degree]).collect();
+    let eval = |(re, im): (f64, f64)| {
+        monic.iter().rev().fold((0.0, 0.0), |(ar, ai), &k| {
+            (ar * re - ai * im + k, ar * im + ai * re)
+        })
+    };
+
+    // distinct complex starting points on a circle enclosing the roots
+    let radius = 1.0 + monic[..degree].iter().fold(0.0_f64, |m, v| m.max(v.abs()));
+    let mut roots: Vec<(f64, f64)> = (0..degree)
+        .map(|i| {
+            let angle = 0.4 + 2.0 * std::f64::consts::PI * i as f64 / degree as f64;
+            (radius * angle.cos(), radius * angle.sin())
+        })
+        .collect();
+
+    for _ in 0..ITERATIONS {
+        let mut shift = 0.0_f64;
+        for i in 0..degree {
+            let (nr, ni) = eval(roots[i]);
+            let (mut dr, mut di) = (1.0, 0.0);
+            for (j, &(rr, ri)) in roots.iter().enumerate() {
+                if i != j {
+                    let (er, ei) = (roots[i].0 - rr, roots[i].1 - ri);
+                    (dr, di) = (dr * er - di * ei, dr * ei + di * er);
+                }
+            }
+            let norm = dr * dr + di * di;
+            if norm < f64::MIN_POSITIVE {
+                continue;
+            }
+            let (qr, qi) = ((nr * dr + ni * di) / norm, (ni * dr - nr * di) / norm);
+            roots[i] = (roots[i].0 - qr, roots[i].1 - qi);
+            shift = shift.max(qr.abs() + qi.abs());
+        }
+        if shift < 1e-14 {
+            break;
+        }
+    }
+
+    roots
+        .into_iter()
+        .filter(|&(re, im)| im.abs() < 1e-6 * (1.0 + re.abs()))
+        .map(|(re, _)| re)
+        .collect()
+}
+
+/// Finds the rotation `R` maximizing `tr(R^T m)` (Horn's quaternion method)
+pub fn nearest_rotation(m: DMat3) -> DMat3 {
+    // s[a][b] = m[b][a]
+    let [[sxx, sxy, sxz], [syx, syy, syz], [szx, szy, szz]] =
+        [m.x_axis, m.y_axis, m.z_axis].map(|col| col.to_array());
+    let n = [
+        [sxx + syy + szz, syz - szy, szx - sxz, sxy - syx],
+        [syz - szy, sxx - syy - szz, sxy + syx, szx + sxz],
+        [szx - sxz, sxy + syx, -sxx + syy - szz, syz + szy],
+        [sxy - syx, szx + sxz, syz + szy, -sxx - syy + szz],
+    ];
+
+    let (_, vectors) = sym_eigen(n);
+    let [w, x, y, z] = vectors[3];
+
+    DMat3::from_quat(DQuat::from_xyzw(x, y, z, w).normalize())
+}
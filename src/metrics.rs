@@ -0,0 +1,140 @@
+//! Accuracy metrics of estimated poses and inlier labels against the ground truth
+
+use crate::types::{ICoord, WCoord};
+use crate::{CameraK, Pose};
+use glam::{Mat3A, Vec3A};
+
+/// Geodesic distance in radians between two rotations, the angle of `estimate * truthᵀ`
+pub fn rotation_error(estimate: &Mat3A, truth: &Mat3A) -> f32 {
+    let q = *estimate * truth.transpose();
+    // 2 sin θ and 2 cos θ, stable near 0 and π unlike acos of the trace alone
+    let sin = Vec3A::new(
+        q.y_axis.z - q.z_axis.y,
+        q.z_axis.x - q.x_axis.z,
+        q.x_axis.y - q.y_axis.x,
+    )
+    .length();
+    let cos = q.x_axis.x + q.y_axis.y + q.z_axis.z - 1.0;
+
+    sin.atan2(cos)
+}
+
+/// Euclidean distance between the translations
+pub fn translation_error(estimate: &Pose, truth: &Pose) -> f32 {
+    (estimate.translation - truth.translation).length()
+}
+
+/// Angle in radians between the translations, which ignores their scale
+pub fn translation_angle_error(estimate: &Pose, truth: &Pose) -> f32 {
+    estimate.translation.angle_between(truth.translation)
+}
+
+/// Root mean square reprojection error in pixels of the correspondences selected by `mask`
+///
+/// Returns `None` when no correspondence is selected or a selected point is behind the camera.
+pub fn reprojection_rmse(
+    k: &CameraK,
+    pose: &Pose,
+    correspondences: &[(ICoord, WCoord)],
+    mask: &[bool],
+) -> Option<f32> {
+    let mut sum = 0.0;
+    let mut n = 0;
+    for ((image, world), _) in correspondences.iter().zip(mask).filter(|(_, &m)| m) {
+        let camera = pose.rotation * world.as_vec3a() + pose.translation;
+        if camera.z <= 0.0 {
+            return None;
+        }
+        let projected = (k.as_mat3a() * (camera / camera.z)).truncate();
+        sum += projected.distance_squared(image.as_ivec2().as_vec2());
+        n += 1;
+    }
+
+    (n > 0).then(|| (sum / n as f32).sqrt())
+}
+
+/// Counts of estimated inlier labels against the ground truth labels
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Confusion {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub true_negatives: usize,
+}
+
+impl Confusion {
+    pub fn new(estimated: &[bool], truth: &[bool]) -> Self {
+        let mut confusion = Confusion::default();
+        for (&e, &t) in estimated.iter().zip(truth) {
+            match (e, t) {
+                (true, true) => confusion.true_positives += 1,
+                (true, false) => confusion.false_positives += 1,
+                (false, true) => confusion.false_negatives += 1,
+                (false, false) => confusion.true_negatives += 1,
+            }
+        }
+
+        confusion
+    }
+
+    /// Fraction of the estimated inliers which are true inliers, 1 when none is estimated
+    pub fn precision(&self) -> f32 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives,
+        )
+    }
+
+    /// Fraction of the true inliers which are estimated inliers, 1 when there is none
+    pub fn recall(&self) -> f32 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives,
+        )
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f32 {
+    if denominator == 0 {
+        1.0
+    } else {
+        numerator as f32 / denominator as f32
+    }
+}
+
+/// Average distance between the model `points` moved by both poses (ADD), none without points
+pub fn add(estimate: &Pose, truth: &Pose, points: &[WCoord]) -> Option<f32> {
+    let distances = points.iter().map(|p| {
+        let p = p.as_vec3a();
+        transform(estimate, p).distance(transform(truth, p))
+    });
+
+    mean(distances, points.len())
+}
+
+/// Average distance from each model point moved by `estimate` to the closest one moved by
+/// `truth` (ADD-S), for symmetric objects; none without points
+pub fn add_s(estimate: &Pose, truth: &Pose, points: &[WCoord]) -> Option<f32> {
+    let moved: Vec<Vec3A> = points
+        .iter()
+        .map(|p| transform(truth, p.as_vec3a()))
+        .collect();
+    let distances = points.iter().map(|p| {
+        let p = transform(estimate, p.as_vec3a());
+        moved
+            .iter()
+            .map(|q| p.distance_squared(*q))
+            .fold(f32::INFINITY, f32::min)
+            .sqrt()
+    });
+
+    mean(distances, points.len())
+}
+
+fn transform(pose: &Pose, point: Vec3A) -> Vec3A {
+    pose.rotation * point + pose.translation
+}
+
+fn mean(values: impl Iterator<Item = f32>, n: usize) -> Option<f32> {
+    (n > 0).then(|| values.sum::<f32>() / n as f32)
+}
@@ -0,0 +1,45 @@
+/// Multiple of the estimated noise scale taken as the inlier threshold
+const SCALE_TO_THRESHOLD: f32 = 3.0;
+/// Fewest residuals to estimate the noise scale from
+const MIN_SAMPLES: usize = 5;
+/// Smallest estimated threshold relative to the configured one, so that exact data does not
+/// leave the search without inliers
+const MIN_RATIO: f32 = 0.1;
+
+/// Distribution of the absolute residuals of inliers under Gaussian noise
+#[derive(Clone, Copy, Debug)]
+pub enum Spread {
+    /// Absolute value of a single Gaussian deviation, e.g. of a plane's tilt
+    HalfNormal,
+    /// Length of an isotropic 2D Gaussian deviation, e.g. the angle between two rays
+    Rayleigh,
+}
+
+impl Spread {
+    /// Scales `residual` so that the median of the residuals is the noise's standard deviation
+    pub fn normalize(self, residual: f32) -> f32 {
+        let median_to_sigma = match self {
+            Spread::HalfNormal => 1.4826,
+            // 1 / sqrt(2 ln 2)
+            Spread::Rayleigh => 0.8493,
+        };
+
+        median_to_sigma * residual.abs()
+    }
+}
+
+/// Estimates an inlier threshold from the residuals of zero-mean Gaussian noise, normalized by
+/// their `Spread`, within `MIN_RATIO` times and once the configured threshold `limit`
+///
+/// The noise scale is estimated by the median, which tolerates residuals of outliers that slipped
+/// into the inlier set. Normalized residuals of either spread share it, so they may be mixed.
+pub fn threshold(mut residuals: Vec<f32>, limit: f32) -> Option<f32> {
+    if residuals.len() < MIN_SAMPLES {
+        return None;
+    }
+
+    let mid = residuals.len() / 2;
+    let (_, median, _) = residuals.select_nth_unstable_by(mid, f32::total_cmp);
+
+    Some((SCALE_TO_THRESHOLD * *median).clamp(MIN_RATIO * limit, limit))
+}
@@ -0,0 +1,83 @@
+use crate::linalg;
+use crate::Pose;
+use glam::{DMat3, DVec3};
+
+/// Solves the perspective-three-point problem (Grunert's method)
+///
+/// `rays` are the directions to the three world points from the camera center. Returns up to
+/// four poses mapping `world` onto the rays.
+pub fn solve(rays: [DVec3; 3], world: [DVec3; 3]) -> Vec<Pose> {
+    let [j1, j2, j3] = rays.map(DVec3::normalize);
+    let [p1, p2, p3] = world;
+
+    let a2 = p2.distance_squared(p3);
+    let b2 = p1.distance_squared(p3);
+    let c2 = p1.distance_squared(p2);
+    if a2.min(b2).min(c2) < f64::EPSILON {
+        return vec![];
+    }
+
+    let (cos_a, cos_b, cos_c) = (j2.dot(j3), j1.dot(j3), j1.dot(j2));
+    let (amc, apc) = ((a2 - c2) / b2, (a2 + c2) / b2);
+    let bmc = (b2 - c2) / b2;
+    let bma = (b2 - a2) / b2;
+
+    // quartic in v = s3 / s1, where s is the distance along each ray
+    let coefficients = [
+        (1.0 + amc).powi(2) - 4.0 * a2 / b2 * cos_c * cos_c,
+        4.0 * (-amc * (1.0 + amc) * cos_b + 2.0 * a2 / b2 * cos_c * cos_c * cos_b
+            - (1.0 - apc) * cos_a * cos_c),
+        2.0 * (amc * amc - 1.0 + 2.0 * amc * amc * cos_b * cos_b + 2.0 * bmc * cos_a * cos_a
+            - 4.0 * apc * cos_a * cos_b * cos_c
+            + 2.0 * bma * cos_c * cos_c),
+        4.0 * (amc * (1.0 - amc) * cos_b - (1.0 - apc) * cos_a * cos_c
+            + 2.0 * c2 / b2 * cos_a * cos_a * cos_b),
+        (amc - 1.0).powi(2) - 4.0 * c2 / b2 * cos_a * cos_a,
+    ];
+
+    linalg::real_roots(coefficients)
+        .into_iter()
+        .filter(|&v| v > 0.0)
+        .filter_map(|v| {
+            let denominator = 2.0 * (cos_c - v * cos_a);
+            if denominator.abs() < f64::EPSILON {
+                return None;
+            }
+            let u = ((-1.0 + amc) * v * v - 2.0 * amc * cos_b * v + 1.0 + amc) / denominator;
+            let s1_2 = c2 / (1.0 + u * u - 2.0 * u * cos_c);
+            if u <= 0.0 || s1_2 <= 0.0 {
+                return None;
+            }
+            let s1 = s1_2.sqrt();
+
+            align(&[j1 * s1, j2 * (u * s1), j3 * (v * s1)], &world)
+        })
+        .collect()
+}
+
+/// Finds the rigid transformation mapping `world` onto `camera` in the least-squares sense
+/// (Horn's quaternion method)
+pub fn align(camera: &[DVec3], world: &[DVec3]) -> Option<Pose> {
+    if camera.len() < 3 || camera.len() != world.len() {
+        return None;
+    }
+    let n = camera.len() as f64;
+    let camera_center = camera.iter().sum::<DVec3>() / n;
+    let world_center = world.iter().sum::<DVec3>() / n;
+
+    let m = camera
+        .iter()
+        .zip(world)
+        .map(|(c, w)| {
+            let (c, w) = (*c - camera_center, *w - world_center);
+            DMat3::from_cols(c * w.x, c * w.y, c * w.z)
+        })
+        .fold(DMat3::ZERO, |sum, outer| sum + outer);
+    let rotation = linalg::nearest_rotation(m);
+    let translation = camera_center - rotation * world_center;
+
+    Some(Pose {
+        rotation: rotation.as_mat3().into(),
+        translation: translation.as_vec3().into(),
+    })
+}
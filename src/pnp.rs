@@ -0,0 +1,430 @@
+use crate::corres::Corres;
+use crate::{linalg, p3p, Pose};
+use glam::{DMat2, DMat3, DQuat, DVec2, DVec3, Mat3A, Vec3A};
+use std::f64::consts::SQRT_2;
+
+/// Second smallest eigenvalue of the DLT's normal matrix, relative to the largest, below which the
+/// projection is not unique, e.g. for coplanar world points
+const DLT_NULL_SPACE: f64 = 1e-8;
+
+/// Correspondence in normalized image coordinates
+struct Point {
+    image: DVec2,
+    world: DVec3,
+    weight: f64,
+}
+
+/// Estimates the pose by EPnP (Lepetit et al.), assuming no outliers
+///
+/// Needs at least 4 non-coplanar world points.
+pub fn epnp(corres: &[Corres]) -> Option<Pose> {
+    let points = points(corres)?;
+    if points.len() < 4 {
+        return None;
+    }
+
+    // control points: the centroid and the principal axes of the world points
+    let n = points.len() as f64;
+    let center = points.iter().map(|p| p.world).sum::<DVec3>() / n;
+    let mut covariance = [[0.0; 3]; 3];
+    for p in &points {
+        let d = (p.world - center).to_array();
+        for (row, a) in covariance.iter_mut().zip(d) {
+            for (value, b) in row.iter_mut().zip(d) {
+                *value += a * b / n;
+            }
+        }
+    }
+    let (variances, axes) = linalg::sym_eigen(covariance);
+    let controls: [DVec3; 4] = [
+        center,
+        center + DVec3::from(axes[0]) * variances[0].max(0.0).sqrt(),
+        center + DVec3::from(axes[1]) * variances[1].max(0.0).sqrt(),
+        center + DVec3::from(axes[2]) * variances[2].max(0.0).sqrt(),
+    ];
+    let basis = DMat3::from_cols(
+        controls[1] - center,
+        controls[2] - center,
+        controls[3] - center,
+    );
+    if basis.determinant().abs() < 1e-12 {
+        return None;
+    }
+    let to_barycentric = basis.inverse();
+
+    // M^T M of the linear system in the control points' camera coordinates
+    let mut mtm = [[0.0; 12]; 12];
+    let alphas: Vec<[f64; 4]> = points
+        .iter()
+        .map(|p| {
+            let b = to_barycentric * (p.world - center);
+            [1.0 - b.x - b.y - b.z, b.x, b.y, b.z]
+        })
+        .collect();
+    for (p, alpha) in points.iter().zip(&alphas) {
+        for image in [
+            DVec3::new(1.0, 0.0, -p.image.x),
+            DVec3::new(0.0, 1.0, -p.image.y),
+        ] {
+            let row: [f64; 12] = std::array::from_fn(|k| alpha[k / 3] * image[k % 3]);
+            accumulate(&mut mtm, &row, p.weight);
+        }
+    }
+    let (_, null_space) = linalg::sym_eigen(mtm);
+
+    let distances: Vec<f64> = control_pairs()
+        .map(|(i, j)| controls[i].distance(controls[j]))
+        .collect();
+    let to_controls = |v: &[f64; 12]| -> [DVec3; 4] {
+        std::array::from_fn(|i| DVec3::new(v[3 * i], v[3 * i + 1], v[3 * i + 2]))
+    };
+
+    // a single null vector, scaled to match the control point distances
+    let v1 = to_controls(&null_space[0]);
+    let (dot, norm) =
+        control_pairs()
+            .zip(&distances)
+            .fold((0.0, 0.0), |(dot, norm), ((i, j), d)| {
+                let dv = v1[i].distance(v1[j]);
+                (dot + dv * d, norm + dv * dv)
+            });
+    let mut candidates = vec![v1.map(|c| c * dot / norm)];
+
+    // two null vectors, by linearizing the distance constraints in the products of the betas
+    let v2 = to_controls(&null_space[1]);
+    let mut ata = [[0.0; 3]; 3];
+    let mut atb = [0.0; 3];
+    for ((i, j), d) in control_pairs().zip(&distances) {
+        let (d1, d2) = (v1[i] - v1[j], v2[i] - v2[j]);
+        let row = [d1.length_squared(), 2.0 * d1.dot(d2), d2.length_squared()];
+        for (a, ra) in row.iter().enumerate() {
+            atb[a] += ra * d * d;
+            for (b, rb) in row.iter().enumerate() {
+                ata[a][b] += ra * rb;
+            }
+        }
+    }
+    if let Some([b11, b12, b22]) = linalg::solve(ata, atb) {
+        let (beta1, beta2) = if b11.abs() > b22.abs() {
+            let beta1 = b11.abs().sqrt();
+            (beta1, b12 / beta1)
+        } else {
+            let beta2 = b22.abs().sqrt();
+            (b12 / beta2, beta2)
+        };
+        if beta1.is_finite() && beta2.is_finite() {
+            candidates.push(std::array::from_fn(|i| v1[i] * beta1 + v2[i] * beta2));
+        }
+    }
+
+    candidates
+        .into_iter()
+        .filter_map(|controls| {
+            let camera: Vec<DVec3> = alphas
+                .iter()
+                .map(|alpha| (0..4).map(|k| controls[k] * alpha[k]).sum())
+                .collect();
+            // the null vectors are defined up to sign; the points lie in front of the camera
+            let sign = camera.iter().map(|c| c.z.signum()).sum::<f64>().signum();
+            let camera: Vec<DVec3> = camera.into_iter().map(|c| c * sign).collect();
+            let world: Vec<DVec3> = points.iter().map(|p| p.world).collect();
+
+            p3p::align(&camera, &world)
+        })
+        .min_by(|a, b| reprojection_cost(&points, a).total_cmp(&reprojection_cost(&points, b)))
+}
+
+/// Estimates the pose by the normalized direct linear transformation, assuming no outliers
+///
+/// Needs at least 6 world points in general position; fails on coplanar ones.
+pub fn dlt(corres: &[Corres]) -> Option<Pose> {
+    let points = points(corres)?;
+    if points.len() < 6 {
+        return None;
+    }
+
+    // similarity transformations moving the centroids to the origin at a unit scale
+    let n = points.len() as f64;
+    let image_center = points.iter().map(|p| p.image).sum::<DVec2>() / n;
+    let world_center = points.iter().map(|p| p.world).sum::<DVec3>() / n;
+    let image_scale = SQRT_2 * n
+        / points
+            .iter()
+            .map(|p| p.image.distance(image_center))
+            .sum::<f64>();
+    let world_scale = 3f64.sqrt() * n
+        / points
+            .iter()
+            .map(|p| p.world.distance(world_center))
+            .sum::<f64>();
+    if !image_scale.is_finite() || !world_scale.is_finite() {
+        return None;
+    }
+
+    let mut ata = [[0.0; 12]; 12];
+    for p in &points {
+        let x = ((p.image - image_center) * image_scale).extend(1.0);
+        let w = ((p.world - world_center) * world_scale).extend(1.0);
+        for (row, image) in [(0, x.x), (1, x.y)] {
+            let mut a = [0.0; 12];
+            for k in 0..4 {
+                a[4 * row + k] = w[k];
+                a[8 + k] = -image * w[k];
+            }
+            accumulate(&mut ata, &a, p.weight);
+        }
+    }
+    let (values, vectors) = linalg::sym_eigen(ata);
+    if values[1] <= DLT_NULL_SPACE * values[11] {
+        return None;
+    }
+    let p = vectors[0];
+
+    // undoes the normalizations: P = T_image^-1 * P' * T_world
+    let m = DMat3::from_cols(
+        DVec3::new(p[0], p[4], p[8]),
+        DVec3::new(p[1], p[5], p[9]),
+        DVec3::new(p[2], p[6], p[10]),
+    );
+    let column = DVec3::new(p[3], p[7], p[11]);
+    let image_inverse = DMat3::from_cols(
+        DVec3::X / image_scale,
+        DVec3::Y / image_scale,
+        image_center.extend(1.0),
+    );
+    let m = m * world_scale;
+    let (mut m, mut column) = (
+        image_inverse * m,
+        image_inverse * (column - m * world_center),
+    );
+    if m.determinant() < 0.0 {
+        m = -m;
+        column = -column;
+    }
+
+    let rotation = linalg::nearest_rotation(m);
+    // tr(R^T M) / 3
+    let scale = (rotation.x_axis.dot(m.x_axis)
+        + rotation.y_axis.dot(m.y_axis)
+        + rotation.z_axis.dot(m.z_axis))
+        / 3.0;
+    if scale.abs() < f64::EPSILON {
+        return None;
+    }
+
+    Some(Pose {
+        rotation: rotation.as_mat3().into(),
+        translation: (column / scale).as_vec3().into(),
+    })
+}
+
+/// Estimates the two poses of the world points on the plane of `normal` by IPPE (Collins and
+/// Bartoli) on their homography, assuming no outliers
+///
+/// Returns the pose of lower reprojection error first. Needs at least 4 coplanar world points.
+pub fn ippe(corres: &[Corres], normal: Vec3A) -> Option<[Pose; 2]> {
+    let points = points(corres)?;
+    if points.len() < 4 {
+        return None;
+    }
+
+    // world = center + frame * (x, y, 0)
+    let normal = normal.as_dvec3().try_normalize()?;
+    let e1 = normal.any_orthonormal_vector();
+    let frame = DMat3::from_cols(e1, normal.cross(e1), normal);
+    let center = points.iter().map(|p| p.world).sum::<DVec3>() / points.len() as f64;
+    let plane: Vec<DVec2> = points
+        .iter()
+        .map(|p| (frame.transpose() * (p.world - center)).truncate())
+        .collect();
+    let h = homography(&points, &plane)?;
+
+    // first-order approximation of the homography about the center of the plane
+    let u0 = DVec2::new(h.z_axis.x, h.z_axis.y) / h.z_axis.z;
+    let jacobian = DMat2::from_cols(
+        (h.x_axis.truncate() - u0 * h.x_axis.z) / h.z_axis.z,
+        (h.y_axis.truncate() - u0 * h.y_axis.z) / h.z_axis.z,
+    );
+
+    // rotates the optical axis onto the ray through the center
+    let rv = DMat3::from_quat(DQuat::from_rotation_arc(
+        DVec3::Z,
+        u0.extend(1.0).normalize(),
+    ));
+    let b = DMat2::from_cols(
+        rv.x_axis.truncate() - u0 * rv.x_axis.z,
+        rv.y_axis.truncate() - u0 * rv.y_axis.z,
+    );
+    if b.determinant().abs() < f64::EPSILON {
+        return None;
+    }
+    let a = b.inverse() * jacobian;
+
+    // the upper-left block of the rotation is `a` scaled by its largest singular value
+    let aat = a * a.transpose();
+    let half_trace = (aat.x_axis.x + aat.y_axis.y) / 2.0;
+    let gamma = (half_trace
+        + (half_trace * half_trace - aat.determinant())
+            .max(0.0)
+            .sqrt())
+    .sqrt();
+    if gamma < f64::EPSILON {
+        return None;
+    }
+    let r22 = a * (1.0 / gamma);
+    let b0 = (1.0 - r22.x_axis.length_squared()).max(0.0).sqrt();
+    let mut b1 = (1.0 - r22.y_axis.length_squared()).max(0.0).sqrt();
+    if r22.x_axis.dot(r22.y_axis) > 0.0 {
+        b1 = -b1;
+    }
+
+    let [first, second] = [1.0, -1.0].map(|sign| {
+        let x = r22.x_axis.extend(sign * b0);
+        let y = r22.y_axis.extend(sign * b1);
+        let rotation = rv * DMat3::from_cols(x, y, x.cross(y)) * frame.transpose();
+        let translation = fit_translation(&points, rotation)?;
+
+        Some(Pose {
+            rotation: rotation.as_mat3().into(),
+            translation: translation.as_vec3().into(),
+        })
+    });
+    let mut poses = [first?, second?];
+    if reprojection_cost(&points, &poses[1]) < reprojection_cost(&points, &poses[0]) {
+        poses.swap(0, 1);
+    }
+
+    Some(poses)
+}
+
+/// Turns `pose` about the line of sight to the center of the inliers, reflecting the normal of
+/// the world plane across it
+///
+/// This is the other pose of the two-fold ambiguity of planar world points, to first order about
+/// the center.
+pub fn flip(pose: &Pose, normal: Vec3A, corres: &[Corres], inliers: &[bool]) -> Pose {
+    let world: Vec<Vec3A> = corres
+        .iter()
+        .zip(inliers)
+        .filter(|(_, &inlier)| inlier)
+        .map(|(c, _)| c.world().as_vec3a())
+        .collect();
+    if world.is_empty() {
+        return *pose;
+    }
+    let center = world.iter().sum::<Vec3A>() / world.len() as f32;
+    let center = pose.rotation * center + pose.translation;
+    let sight = center.normalize_or_zero();
+    let normal = pose.rotation * normal.normalize_or_zero();
+    let Some(axis) = normal.cross(sight).try_normalize() else {
+        // seen face-on, both poses coincide
+        return *pose;
+    };
+    let turn = Mat3A::from_axis_angle(axis.into(), 2.0 * normal.angle_between(sight));
+
+    Pose {
+        rotation: turn * pose.rotation,
+        translation: turn * (pose.translation - center) + center,
+    }
+}
+
+/// Estimates the homography from the plane coordinates to the normalized image coordinates by
+/// the normalized DLT
+fn homography(points: &[Point], plane: &[DVec2]) -> Option<DMat3> {
+    let n = points.len() as f64;
+    let image_center = points.iter().map(|p| p.image).sum::<DVec2>() / n;
+    let image_scale = SQRT_2 * n
+        / points
+            .iter()
+            .map(|p| p.image.distance(image_center))
+            .sum::<f64>();
+    // the plane coordinates are centered already
+    let plane_scale = SQRT_2 * n / plane.iter().map(|q| q.length()).sum::<f64>();
+    if !image_scale.is_finite() || !plane_scale.is_finite() {
+        return None;
+    }
+
+    let mut ata = [[0.0; 9]; 9];
+    for (p, q) in points.iter().zip(plane) {
+        let u = (p.image - image_center) * image_scale;
+        let q = (*q * plane_scale).extend(1.0);
+        for (row, image) in [(0, u.x), (1, u.y)] {
+            let mut a = [0.0; 9];
+            for k in 0..3 {
+                a[3 * row + k] = q[k];
+                a[6 + k] = -image * q[k];
+            }
+            accumulate(&mut ata, &a, p.weight);
+        }
+    }
+    let (_, vectors) = linalg::sym_eigen(ata);
+
+    // undoes the normalizations: H = T_image^-1 * H' * T_plane
+    let h = DMat3::from_cols_array(&vectors[0]).transpose();
+    let image_inverse = DMat3::from_cols(
+        DVec3::X / image_scale,
+        DVec3::Y / image_scale,
+        image_center.extend(1.0),
+    );
+
+    Some(image_inverse * h * DMat3::from_diagonal(DVec3::new(plane_scale, plane_scale, 1.0)))
+}
+
+/// Computes the translation minimizing the distances of `R * world + t` to the rays
+fn fit_translation(points: &[Point], rotation: DMat3) -> Option<DVec3> {
+    let mut a = DMat3::ZERO;
+    let mut b = DVec3::ZERO;
+    for p in points {
+        let ray = p.image.extend(1.0).normalize();
+        // projects onto the plane orthogonal to the ray
+        let projection = DMat3::IDENTITY - DMat3::from_cols(ray * ray.x, ray * ray.y, ray * ray.z);
+
+        a += projection * p.weight;
+        b -= projection * (rotation * p.world) * p.weight;
+    }
+    if a.determinant().abs() < f64::EPSILON {
+        return None;
+    }
+
+    Some(a.inverse() * b)
+}
+
+fn points(corres: &[Corres]) -> Option<Vec<Point>> {
+    corres
+        .iter()
+        .map(|c| {
+            let ray = c.projected().as_vec3a().as_dvec3();
+            (ray.z.abs() > f64::EPSILON).then(|| Point {
+                image: ray.truncate() / ray.z,
+                world: c.world().as_vec3a().as_dvec3(),
+                weight: c.weight() as f64,
+            })
+        })
+        .collect()
+}
+
+fn control_pairs() -> impl Iterator<Item = (usize, usize)> {
+    (0..4).flat_map(|i| ((i + 1)..4).map(move |j| (i, j)))
+}
+
+/// Adds `weight * row^T row`
+fn accumulate<const N: usize>(ata: &mut [[f64; N]; N], row: &[f64; N], weight: f64) {
+    for (a, ra) in ata.iter_mut().zip(row) {
+        for (value, rb) in a.iter_mut().zip(row) {
+            *value += weight * ra * rb;
+        }
+    }
+}
+
+fn reprojection_cost(points: &[Point], pose: &Pose) -> f64 {
+    let rotation = glam::Mat3::from(pose.rotation).as_dmat3();
+    let translation = pose.translation.as_dvec3();
+
+    points
+        .iter()
+        .map(|p| {
+            let x = rotation * p.world + translation;
+            p.weight * (x.truncate() / x.z).distance_squared(p.image)
+        })
+        .sum()
+}
@@ -0,0 +1,274 @@
+//! Text format of correspondence datasets
+//!
+//! A problem file is read line by line. Blank lines and anything after `#` are ignored, and
+//! every other line is a record of whitespace-separated fields:
+//!
+//! ```text
+//! # camera <fx> <fy> <cx> <cy>            intrinsics, exactly once
+//! camera 718.856 718.856 607.1928 185.2157
+//! # pose <r00> <r01> ... <r22> <tx> <ty> <tz>
+//! #   optional ground truth, world to camera, rotation in row-major order
+//! pose 1 0 0 0 1 0 0 0 1 0.1 -0.2 0.3
+//! # point <u> <v> <x> <y> <z> [<label>]
+//! #   image point in pixels, world point, and optionally 1 (inlier) or 0 (outlier);
+//! #   either every point is labeled or none is
+//! point 640 200 0.5 -0.3 5.2 1
+//! point 12 370 -1.2 0.8 6.0 0
+//! ```
+//!
+//! With the `serde` feature, `Problem` can be stored as JSON as well.
+
+use crate::types::{ICoord, WCoord};
+use crate::{CameraK, Pose, PoseEstimator};
+use glam::{IVec2, Mat3A, Vec3A};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// Correspondences observed by a camera, with the optional ground truth
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Problem {
+    pub k: CameraK,
+    pub correspondences: Vec<(ICoord, WCoord)>,
+    /// Pose the image points were observed from
+    pub ground_truth: Option<Pose>,
+    /// Whether each correspondence is an inlier, in the order of `correspondences`
+    pub inliers: Option<Vec<bool>>,
+}
+
+#[derive(Debug)]
+pub enum ProblemError {
+    Io(io::Error),
+    /// Malformed record at the 1-based `line`
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// Records inconsistent across the file
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ProblemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProblemError::Io(error) => write!(f, "{error}"),
+            ProblemError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            ProblemError::Invalid(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for ProblemError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProblemError::Io(error) => Some(error),
+            ProblemError::Parse { .. } | ProblemError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ProblemError {
+    fn from(value: io::Error) -> Self {
+        ProblemError::Io(value)
+    }
+}
+
+impl Problem {
+    pub fn read(reader: impl BufRead) -> Result<Problem, ProblemError> {
+        let mut k = None;
+        let mut correspondences = vec![];
+        let mut ground_truth = None;
+        let mut labels = vec![];
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let error = |message: String| ProblemError::Parse {
+                line: i + 1,
+                message,
+            };
+
+            let mut fields = line.split('#').next().unwrap_or("").split_whitespace();
+            let Some(record) = fields.next() else {
+                continue;
+            };
+            let values = fields
+                .map(str::parse::<f32>)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| error(e.to_string()))?;
+
+            match (record, values.as_slice()) {
+                ("camera", &[fx, fy, cx, cy]) => {
+                    if k.replace(CameraK { fx, fy, cx, cy }).is_some() {
+                        return Err(error("duplicate camera record".into()));
+                    }
+                }
+                ("pose", &[r00, r01, r02, r10, r11, r12, r20, r21, r22, tx, ty, tz]) => {
+                    let pose = Pose {
+                        rotation: Mat3A::from_cols_array(&[
+                            r00, r10, r20, r01, r11, r21, r02, r12, r22,
+                        ]),
+                        translation: Vec3A::new(tx, ty, tz),
+                    };
+                    if ground_truth.replace(pose).is_some() {
+                        return Err(error("duplicate pose record".into()));
+                    }
+                }
+                ("point", &[u, v, x, y, z, ref label @ ..]) if label.len() <= 1 => {
+                    if u.fract() != 0.0 || v.fract() != 0.0 {
+                        return Err(error("image point must be in integer pixels".into()));
+                    }
+                    let label = match *label {
+                        [] => None,
+                        [l] if l == 0.0 || l == 1.0 => Some(l == 1.0),
+                        _ => return Err(error("label must be 0 or 1".into())),
+                    };
+                    labels.push(label);
+                    correspondences.push((
+                        IVec2::new(u as i32, v as i32).into(),
+                        WCoord::from(Vec3A::new(x, y, z)),
+                    ));
+                }
+                ("camera" | "pose" | "point", _) => {
+                    return Err(error(format!("wrong number of fields for `{record}`")));
+                }
+                _ => return Err(error(format!("unknown record `{record}`"))),
+            }
+        }
+
+        let k = k.ok_or(ProblemError::Invalid("missing camera record"))?;
+        let inliers = if labels.iter().all(Option::is_none) {
+            None
+        } else {
+            let inliers = labels.into_iter().collect::<Option<Vec<_>>>();
+            Some(inliers.ok_or(ProblemError::Invalid(
+                "either every point or none has to be labeled",
+            ))?)
+        };
+
+        Ok(Problem {
+            k,
+            correspondences,
+            ground_truth,
+            inliers,
+        })
+    }
+
+    /// Writes the problem, failing if `inliers` does not label every correspondence
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        if self
+            .inliers
+            .as_ref()
+            .is_some_and(|inliers| inliers.len() != self.correspondences.len())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "either every point or none has to be labeled",
+            ));
+        }
+
+        let CameraK { fx, fy, cx, cy } = self.k;
+        writeln!(writer, "camera {fx} {fy} {cx} {cy}")?;
+
+        if let Some(Pose {
+            rotation,
+            translation,
+        }) = self.ground_truth
+        {
+            let r = rotation.transpose().to_cols_array();
+            let t = translation.to_array();
+            write!(writer, "pose")?;
+            for value in r.iter().chain(&t) {
+                write!(writer, " {value}")?;
+            }
+            writeln!(writer)?;
+        }
+
+        for (i, (image, world)) in self.correspondences.iter().enumerate() {
+            let image = image.as_ivec2();
+            let world = world.as_vec3a();
+            write!(
+                writer,
+                "point {} {} {} {} {}",
+                image.x, image.y, world.x, world.y, world.z
+            )?;
+            if let Some(inliers) = &self.inliers {
+                write!(writer, " {}", u8::from(inliers[i]))?;
+            }
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds the correspondences to `estimator`
+    pub fn add_to(&self, estimator: &mut (impl PoseEstimator + ?Sized)) {
+        estimator.reserve_correspondence(self.correspondences.len());
+        for &(image, world) in &self.correspondences {
+            estimator.add_correspondence(image, world, &self.k);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LABELED: &str = "\
+camera 718.856 718.856 607.1928 185.2157
+pose 0.36 0.48 -0.8 -0.8 0.6 0 0.48 0.64 0.6 0.1 -0.2 0.3
+point 640 200 0.5 -0.3 5.2 1
+point 12 370 -1.2 0.8 6.0 0
+";
+    const UNLABELED: &str = "\
+# intrinsics only
+camera 500 501 320.5 240.25
+point 1 2 0.1 0.2 0.3
+";
+
+    fn round_trip(text: &str) -> (Problem, Problem) {
+        let problem = Problem::read(text.as_bytes()).unwrap();
+        let mut written = vec![];
+        problem.write(&mut written).unwrap();
+
+        (problem, Problem::read(written.as_slice()).unwrap())
+    }
+
+    #[test]
+    fn reads_what_it_writes() {
+        for text in [LABELED, UNLABELED] {
+            let (problem, reread) = round_trip(text);
+
+            let k = |k: CameraK| [k.fx, k.fy, k.cx, k.cy];
+            assert_eq!(k(problem.k), k(reread.k));
+            let points = |problem: &Problem| -> Vec<(IVec2, Vec3A)> {
+                problem
+                    .correspondences
+                    .iter()
+                    .map(|(image, world)| (image.as_ivec2(), world.as_vec3a()))
+                    .collect()
+            };
+            assert_eq!(points(&problem), points(&reread));
+            let pose = |pose: Option<Pose>| pose.map(|pose| (pose.rotation, pose.translation));
+            assert_eq!(pose(problem.ground_truth), pose(reread.ground_truth));
+            assert_eq!(problem.inliers, reread.inliers);
+        }
+    }
+
+    #[test]
+    fn keeps_the_labels_and_the_pose() {
+        let (_, reread) = round_trip(LABELED);
+
+        assert_eq!(reread.inliers, Some(vec![true, false]));
+        let pose = reread.ground_truth.unwrap();
+        assert_eq!(pose.rotation.row(0), Vec3A::new(0.36, 0.48, -0.8));
+        assert_eq!(pose.translation, Vec3A::new(0.1, -0.2, 0.3));
+    }
+
+    #[test]
+    fn refuses_partial_labels() {
+        let mut problem = Problem::read(LABELED.as_bytes()).unwrap();
+        problem.inliers = Some(vec![true]);
+
+        assert!(problem.write(io::sink()).is_err());
+    }
+}
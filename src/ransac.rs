@@ -0,0 +1,261 @@
+use crate::corres::{Constraint, CorresPair};
+use crate::degeneracy::Degeneracy;
+use crate::estimator::Setup;
+use crate::types::{ICoord, WCoord};
+use crate::{p3p, pnp, refine};
+use crate::{Alternative, CameraK, Cost, Failure, Pose, PoseEstimator, SearchStats, Solution};
+use glam::{Mat2, Mat3A, Vec3A};
+
+const DEFAULT_MAX_ITERATIONS: usize = 1000;
+const DEFAULT_CONFIDENCE: f64 = 0.99;
+const DEFAULT_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+const SAMPLE_SIZE: usize = 3;
+
+/// RANSAC over minimal P3P samples, as a fast heuristic baseline to `Solver`
+///
+/// Its rotation is a good `Solver::hint`, seeding the lower bound of the certified search.
+pub struct RansacSolver {
+    setup: Setup,
+    cost: Cost,
+    max_iterations: usize,
+    confidence: f64,
+    seed: u64,
+}
+
+impl RansacSolver {
+    pub fn new(r_threshold: f32, t_threshold: f32) -> Self {
+        RansacSolver {
+            setup: Setup::new(r_threshold, t_threshold),
+            cost: Cost::default(),
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            confidence: DEFAULT_CONFIDENCE,
+            seed: DEFAULT_SEED,
+        }
+    }
+
+    /// Inlier threshold in pixels, replacing `r_threshold` and `t_threshold` when set.
+    pub fn pixel_threshold(&mut self) -> &mut Option<f32> {
+        self.setup.pixel_threshold()
+    }
+
+    /// Cost the `Solution::score` is measured with, to compare against `Solver`
+    pub fn cost(&mut self) -> &mut Cost {
+        &mut self.cost
+    }
+
+    pub fn max_iterations(&mut self) -> &mut usize {
+        &mut self.max_iterations
+    }
+
+    /// Probability of drawing an all-inlier sample, which decides when to stop early
+    pub fn confidence(&mut self) -> &mut f64 {
+        &mut self.confidence
+    }
+
+    /// Seed of the sampler; the result is deterministic for a given seed
+    pub fn seed(&mut self) -> &mut u64 {
+        &mut self.seed
+    }
+
+    /// Solves as `solve`, returning the unrefined pose, which is the identity when `solve` finds
+    /// none
+    pub fn pose(&self) -> (Mat3A, Vec3A) {
+        let Solution { pose, .. } = self.solve();
+
+        (pose.rotation, pose.translation)
+    }
+
+    /// Solves the pose and refines it on the inliers of the best hypothesis
+    pub fn solve(&self) -> Solution {
+        let _span = span!("ransac_solve");
+        let degeneracy = Degeneracy::check(&self.setup.corres, &[], &[]);
+        if degeneracy.is_fatal() {
+            return Solution::undetermined(
+                [self.setup.corres.len(), 0, 0],
+                self.setup.rot_threshold(),
+                self.setup.ray_threshold(),
+                degeneracy,
+                Failure::Degenerate,
+            );
+        }
+
+        let (pose, inliers) = self.best_hypothesis();
+        let refined = {
+            let _span = span!("refinement");
+            refine::refine(&self.setup.corres, &inliers, pose)
+        };
+        let constraints: Vec<_> = CorresPair::make_pairs(&self.setup.corres)
+            .iter()
+            .map(CorresPair::constraint)
+            .collect();
+        let rot_threshold = self.setup.rot_threshold();
+        let score = Constraint::score(&constraints, &pose.rotation, rot_threshold, self.cost);
+        let plane = degeneracy.plane_normal();
+
+        let mut solution = Solution {
+            pose,
+            refined,
+            inliers,
+            line_inliers: vec![],
+            vanishing_inliers: vec![],
+            score,
+            rot_threshold,
+            ray_threshold: self.setup.ray_threshold(),
+            stats: SearchStats::default(),
+            degeneracy,
+            alternative: None,
+            failure: None,
+        };
+        solution.alternative = plane.map(|normal| {
+            let start = pnp::flip(
+                &solution.refined,
+                normal,
+                &self.setup.corres,
+                &solution.inliers,
+            );
+            Alternative::new(
+                &solution,
+                &self.setup.corres,
+                &[],
+                &[],
+                &constraints,
+                start,
+                self.cost,
+            )
+        });
+
+        solution
+    }
+
+    /// Returns the hypothesis with the largest weighted support, and its inliers
+    fn best_hypothesis(&self) -> (Pose, Vec<bool>) {
+        let _span = span!("hypotheses");
+        let threshold = self.setup.ray_threshold();
+
+        let mut best = Pose {
+            rotation: Mat3A::IDENTITY,
+            translation: Vec3A::ZERO,
+        };
+        let mut best_inliers = vec![false; self.setup.corres.len()];
+        if self.setup.corres.len() < SAMPLE_SIZE {
+            return (best, best_inliers);
+        }
+
+        let total = refine::support(&self.setup.corres, &vec![true; self.setup.corres.len()]);
+        let mut best_support = 0.0;
+        let mut rng = XorShift::new(self.seed);
+        let mut iterations = self.max_iterations;
+        let mut i = 0;
+
+        while i < iterations {
+            i += 1;
+            let sample = rng.sample(self.setup.corres.len());
+            let rays = sample.map(|j| self.setup.corres[j].projected().as_vec3a().as_dvec3());
+            let world = sample.map(|j| self.setup.corres[j].world().as_vec3a().as_dvec3());
+
+            for pose in p3p::solve(rays, world) {
+                let mask = refine::ray_inliers(&self.setup.corres, &pose, threshold);
+                let support = refine::support(&self.setup.corres, &mask);
+                if support > best_support {
+                    best = pose;
+                    best_inliers = mask;
+                    best_support = support;
+                    iterations = iterations.min(required_iterations(
+                        (best_support / total) as f64,
+                        self.confidence,
+                    ));
+                }
+            }
+        }
+        event!(iterations = i, support = best_support, "sampled");
+
+        (best, best_inliers)
+    }
+}
+
+impl PoseEstimator for RansacSolver {
+    fn reset_correspondence(&mut self) {
+        self.setup.corres.clear();
+    }
+
+    fn reserve_correspondence(&mut self, n: usize) {
+        self.setup.corres.reserve(n);
+    }
+
+    fn add_correspondence_with_covariance(
+        &mut self,
+        projected: ICoord,
+        world: WCoord,
+        k: &CameraK,
+        weight: f32,
+        covariance: Mat2,
+    ) {
+        self.setup
+            .add_correspondence(projected, world, k, weight, &covariance);
+    }
+
+    fn estimate(&self) -> Solution {
+        self.solve()
+    }
+
+    fn reselect(&self, solution: &mut Solution) -> bool {
+        let corres = &self.setup.corres;
+        let inliers = refine::ray_inliers(corres, &solution.refined, solution.ray_threshold);
+        if inliers == solution.inliers {
+            return false;
+        }
+
+        solution.refined = refine::refine(corres, &inliers, solution.refined);
+        solution.inliers = inliers;
+
+        true
+    }
+}
+
+/// Number of samples drawing an all-inlier one with probability `confidence`
+fn required_iterations(inlier_ratio: f64, confidence: f64) -> usize {
+    let all_inlier = inlier_ratio.powi(SAMPLE_SIZE as i32);
+    if all_inlier >= 1.0 {
+        return 1;
+    }
+
+    let n = (1.0 - confidence).ln() / (1.0 - all_inlier).ln();
+    if n.is_finite() {
+        n.ceil().max(1.0) as usize
+    } else {
+        usize::MAX
+    }
+}
+
+/// xorshift64* generator, so the solver needs no random number dependency
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // the state must not be zero
+        XorShift(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Draws `SAMPLE_SIZE` distinct indices below `n`
+    fn sample(&mut self, n: usize) -> [usize; SAMPLE_SIZE] {
+        let mut sample = [0; SAMPLE_SIZE];
+        let mut drawn = 0;
+
+        while drawn < SAMPLE_SIZE {
+            let index = (self.next() % n as u64) as usize;
+            if !sample[..drawn].contains(&index) {
+                sample[drawn] = index;
+                drawn += 1;
+            }
+        }
+
+        sample
+    }
+}
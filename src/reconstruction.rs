@@ -0,0 +1,87 @@
+//! Importers of Structure-from-Motion reconstructions
+//!
+//! Every registered image becomes a `Problem` of its observations of the reconstructed points,
+//! with the reconstruction's pose as the ground truth to measure the solver against.
+
+mod bundler;
+mod colmap;
+
+pub use bundler::read_bundler;
+pub use colmap::{read_colmap, read_colmap_binary, read_colmap_text};
+
+use crate::problem::Problem;
+use crate::Distortion;
+use std::fmt;
+use std::io;
+
+/// Registered image of a reconstruction
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub name: String,
+    /// Distortion of the camera, already removed from the image points of `problem`
+    pub distortion: Distortion,
+    pub problem: Problem,
+}
+
+#[derive(Debug)]
+pub enum ReconstructionError {
+    Io(io::Error),
+    /// Malformed file content
+    Parse(String),
+    /// Camera model which cannot be mapped to `CameraK` and `Distortion`
+    UnsupportedModel(String),
+}
+
+impl fmt::Display for ReconstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReconstructionError::Io(error) => write!(f, "{error}"),
+            ReconstructionError::Parse(message) => write!(f, "{message}"),
+            ReconstructionError::UnsupportedModel(model) => {
+                write!(f, "unsupported camera model `{model}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReconstructionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReconstructionError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReconstructionError {
+    fn from(value: io::Error) -> Self {
+        ReconstructionError::Io(value)
+    }
+}
+
+/// Parses the next whitespace-separated field
+fn parse<'a, T: std::str::FromStr>(
+    fields: &mut impl Iterator<Item = &'a str>,
+    what: &str,
+) -> Result<T, ReconstructionError> {
+    let field = fields
+        .next()
+        .ok_or_else(|| ReconstructionError::Parse(format!("missing {what}")))?;
+
+    field
+        .parse()
+        .map_err(|_| ReconstructionError::Parse(format!("invalid {what} `{field}`")))
+}
+
+/// Parses the next `N` whitespace-separated fields
+fn parse_array<'a, T: std::str::FromStr + Default + Copy, const N: usize>(
+    fields: &mut impl Iterator<Item = &'a str>,
+    what: &str,
+) -> Result<[T; N], ReconstructionError> {
+    let mut values = [T::default(); N];
+    for value in &mut values {
+        *value = parse(fields, what)?;
+    }
+
+    Ok(values)
+}
@@ -0,0 +1,94 @@
+use super::{parse, parse_array, Image, ReconstructionError};
+use crate::problem::Problem;
+use crate::types::WCoord;
+use crate::{CameraK, Distortion, Pose};
+use glam::{Mat3A, Vec2, Vec3A};
+use std::io::BufRead;
+
+/// Reads a Bundler `bundle.out` (v0.3) file
+///
+/// Bundler stores no image names nor sizes: the images are named by their index, and the
+/// keypoints, which are relative to the image center, keep the center at the origin of the
+/// returned intrinsics. Bundler's camera looks down -Z with +Y up, and is converted to +Z forward
+/// with +Y down. Unregistered cameras, which have a zero focal length, are skipped.
+pub fn read_bundler(reader: impl BufRead) -> Result<Vec<Image>, ReconstructionError> {
+    let mut content = String::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.starts_with('#') {
+            content.push_str(&line);
+            content.push('\n');
+        }
+    }
+    let fields = &mut content.split_whitespace();
+
+    let n_cameras: usize = parse(fields, "camera count")?;
+    let n_points: usize = parse(fields, "point count")?;
+
+    // Bundler to our camera frame
+    let flip = Mat3A::from_diagonal(Vec3A::new(1.0, -1.0, -1.0).into());
+    let mut cameras = Vec::with_capacity(n_cameras);
+    for _ in 0..n_cameras {
+        let [f, k1, k2] = parse_array(fields, "camera")?;
+        let r = parse_array(fields, "rotation")?;
+        let t: [f32; 3] = parse_array(fields, "translation")?;
+
+        let pose = Pose {
+            rotation: flip * Mat3A::from_cols_array(&r).transpose(),
+            translation: flip * Vec3A::from(t),
+        };
+        let distortion = Distortion::BrownConrady {
+            k: [k1, k2, 0.0, 0.0, 0.0, 0.0],
+            p: [0.0; 2],
+        };
+        cameras.push((f, pose, distortion));
+    }
+
+    let mut correspondences = vec![vec![]; n_cameras];
+    for _ in 0..n_points {
+        let world = WCoord::from(Vec3A::from(parse_array::<f32, 3>(fields, "point")?));
+        let _rgb: [u8; 3] = parse_array(fields, "color")?;
+
+        let n_views: usize = parse(fields, "view count")?;
+        for _ in 0..n_views {
+            let camera: usize = parse(fields, "camera index")?;
+            let _key: usize = parse(fields, "key index")?;
+            let x: f32 = parse(fields, "keypoint")?;
+            let y: f32 = parse(fields, "keypoint")?;
+            let views = correspondences.get_mut(camera).ok_or_else(|| {
+                ReconstructionError::Parse(format!("view of the missing camera {camera}"))
+            })?;
+            views.push((Vec2::new(x, -y), world));
+        }
+    }
+
+    Ok(cameras
+        .into_iter()
+        .zip(correspondences)
+        .enumerate()
+        .filter(|(_, ((f, _, _), _))| *f != 0.0)
+        .map(|(i, ((f, pose, distortion), views))| {
+            let k = CameraK {
+                fx: f,
+                fy: f,
+                cx: 0.0,
+                cy: 0.0,
+            };
+            let correspondences = views
+                .into_iter()
+                .map(|(keypoint, world)| (distortion.undistort_pixel(&k, keypoint), world))
+                .collect();
+
+            Image {
+                name: i.to_string(),
+                distortion,
+                problem: Problem {
+                    k,
+                    correspondences,
+                    ground_truth: Some(pose),
+                    inliers: None,
+                },
+            }
+        })
+        .collect())
+}
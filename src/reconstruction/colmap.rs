@@ -0,0 +1,364 @@
+use super::{parse, parse_array, Image, ReconstructionError};
+use crate::problem::Problem;
+use crate::types::WCoord;
+use crate::{CameraK, Distortion, Pose};
+use glam::{DQuat, DVec3, Mat3A, Vec2, Vec3A};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// COLMAP's id of a 2D point without a 3D point
+const INVALID_POINT3D_ID: u64 = u64::MAX;
+
+struct Camera {
+    model: &'static str,
+    params: Vec<f64>,
+}
+
+struct ImageRecord {
+    name: String,
+    camera_id: u32,
+    rotation: DQuat,
+    translation: DVec3,
+    /// Keypoints and the ids of their 3D points
+    points: Vec<(Vec2, Option<u64>)>,
+}
+
+/// Reads a COLMAP model from `dir`, in the binary format if `cameras.bin` exists
+pub fn read_colmap(dir: impl AsRef<Path>) -> Result<Vec<Image>, ReconstructionError> {
+    let dir = dir.as_ref();
+    if dir.join("cameras.bin").exists() {
+        read_colmap_binary(dir)
+    } else {
+        read_colmap_text(dir)
+    }
+}
+
+/// Reads `cameras.txt`, `images.txt` and `points3D.txt` from `dir`
+pub fn read_colmap_text(dir: impl AsRef<Path>) -> Result<Vec<Image>, ReconstructionError> {
+    let dir = dir.as_ref();
+    let lines = |name: &str| -> Result<Vec<String>, ReconstructionError> {
+        let file = BufReader::new(File::open(dir.join(name))?);
+        let mut lines = vec![];
+        for line in file.lines() {
+            let line = line?;
+            if !line.starts_with('#') {
+                lines.push(line);
+            }
+        }
+        Ok(lines)
+    };
+
+    let mut cameras = HashMap::new();
+    for line in lines("cameras.txt")? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let id = parse(&mut fields, "camera id")?;
+        let model_name: String = parse(&mut fields, "camera model")?;
+        let model = MODELS
+            .iter()
+            .find(|(name, _)| *name == model_name)
+            .map(|(name, _)| *name)
+            .ok_or(ReconstructionError::UnsupportedModel(model_name))?;
+        let _width: u64 = parse(&mut fields, "image width")?;
+        let _height: u64 = parse(&mut fields, "image height")?;
+        let params = fields
+            .map(|field| {
+                field.parse().map_err(|_| {
+                    ReconstructionError::Parse(format!("invalid camera parameter `{field}`"))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        cameras.insert(id, Camera { model, params });
+    }
+
+    // every image spans two lines, the second of which is empty without keypoints
+    let mut images = vec![];
+    let image_lines = lines("images.txt")?;
+    let mut image_lines = image_lines.iter();
+    while let Some(line) = image_lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = line.split_whitespace().skip(1);
+        let [qw, qx, qy, qz, tx, ty, tz] = parse_array(&mut fields, "pose")?;
+        let camera_id = parse(&mut fields, "camera id")?;
+        let name = fields.collect::<Vec<_>>().join(" ");
+
+        let keypoints = image_lines.next().map(String::as_str).unwrap_or("");
+        let fields: Vec<&str> = keypoints.split_whitespace().collect();
+        if !fields.len().is_multiple_of(3) {
+            return Err(ReconstructionError::Parse(format!(
+                "keypoints of `{name}` are not triples"
+            )));
+        }
+        let points = fields
+            .chunks_exact(3)
+            .map(|point| {
+                let mut point = point.iter().copied();
+                let x = parse(&mut point, "keypoint")?;
+                let y = parse(&mut point, "keypoint")?;
+                let id: i64 = parse(&mut point, "point id")?;
+                Ok((Vec2::new(x, y), u64::try_from(id).ok()))
+            })
+            .collect::<Result<_, ReconstructionError>>()?;
+
+        images.push(ImageRecord {
+            name,
+            camera_id,
+            rotation: DQuat::from_xyzw(qx, qy, qz, qw),
+            translation: DVec3::new(tx, ty, tz),
+            points,
+        });
+    }
+
+    let mut points = HashMap::new();
+    for line in lines("points3D.txt")? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let id = parse(&mut fields, "point id")?;
+        let point: [f32; 3] = parse_array(&mut fields, "point")?;
+        points.insert(id, Vec3A::from(point));
+    }
+
+    assemble(&cameras, images, &points)
+}
+
+/// Reads `cameras.bin`, `images.bin` and `points3D.bin` from `dir`
+pub fn read_colmap_binary(dir: impl AsRef<Path>) -> Result<Vec<Image>, ReconstructionError> {
+    let dir = dir.as_ref();
+    let open = |name: &str| -> Result<Binary<BufReader<File>>, ReconstructionError> {
+        Ok(Binary(BufReader::new(File::open(dir.join(name))?)))
+    };
+
+    let mut cameras = HashMap::new();
+    let mut file = open("cameras.bin")?;
+    for _ in 0..file.u64()? {
+        let id = file.u32()?;
+        let model_id = file.u32()? as usize;
+        let &(model, n_params) = MODELS
+            .get(model_id)
+            .ok_or_else(|| ReconstructionError::UnsupportedModel(format!("model id {model_id}")))?;
+        let _width = file.u64()?;
+        let _height = file.u64()?;
+        let params = (0..n_params)
+            .map(|_| file.f64())
+            .collect::<Result<_, _>>()?;
+        cameras.insert(id, Camera { model, params });
+    }
+
+    let mut images = vec![];
+    let mut file = open("images.bin")?;
+    for _ in 0..file.u64()? {
+        let _id = file.u32()?;
+        let [qw, qx, qy, qz] = file.f64s()?;
+        let [tx, ty, tz] = file.f64s()?;
+        let camera_id = file.u32()?;
+        let name = file.string()?;
+        let points = (0..file.u64()?)
+            .map(|_| {
+                let x = file.f64()? as f32;
+                let y = file.f64()? as f32;
+                let id = file.u64()?;
+                Ok((Vec2::new(x, y), (id != INVALID_POINT3D_ID).then_some(id)))
+            })
+            .collect::<Result<_, ReconstructionError>>()?;
+
+        images.push(ImageRecord {
+            name,
+            camera_id,
+            rotation: DQuat::from_xyzw(qx, qy, qz, qw),
+            translation: DVec3::new(tx, ty, tz),
+            points,
+        });
+    }
+
+    let mut points = HashMap::new();
+    let mut file = open("points3D.bin")?;
+    for _ in 0..file.u64()? {
+        let id = file.u64()?;
+        let point: [f64; 3] = file.f64s()?;
+        let _rgb = file.bytes::<3>()?;
+        let _error = file.f64()?;
+        for _ in 0..file.u64()? {
+            let _image_id = file.u32()?;
+            let _point2d_index = file.u32()?;
+        }
+        points.insert(id, DVec3::from(point).as_vec3().into());
+    }
+
+    assemble(&cameras, images, &points)
+}
+
+/// Names and parameter counts of COLMAP's camera models, indexed by model id
+const MODELS: [(&str, usize); 11] = [
+    ("SIMPLE_PINHOLE", 3),
+    ("PINHOLE", 4),
+    ("SIMPLE_RADIAL", 4),
+    ("RADIAL", 5),
+    ("OPENCV", 8),
+    ("OPENCV_FISHEYE", 8),
+    ("FULL_OPENCV", 12),
+    ("FOV", 5),
+    ("SIMPLE_RADIAL_FISHEYE", 4),
+    ("RADIAL_FISHEYE", 5),
+    ("THIN_PRISM_FISHEYE", 12),
+];
+
+/// Maps a COLMAP camera onto the intrinsics and the distortion
+fn intrinsics(camera: &Camera) -> Result<(CameraK, Distortion), ReconstructionError> {
+    let expected = MODELS
+        .iter()
+        .find(|(name, _)| *name == camera.model)
+        .map_or(0, |&(_, n)| n);
+    if camera.params.len() != expected {
+        return Err(ReconstructionError::Parse(format!(
+            "{} camera has {} parameters instead of {expected}",
+            camera.model,
+            camera.params.len()
+        )));
+    }
+
+    let p: Vec<f32> = camera.params.iter().map(|&v| v as f32).collect();
+    let k = |fx, fy, cx, cy| CameraK { fx, fy, cx, cy };
+    let radial = |k1, k2| Distortion::BrownConrady {
+        k: [k1, k2, 0.0, 0.0, 0.0, 0.0],
+        p: [0.0; 2],
+    };
+
+    Ok(match camera.model {
+        "SIMPLE_PINHOLE" => (k(p[0], p[0], p[1], p[2]), Distortion::None),
+        "PINHOLE" => (k(p[0], p[1], p[2], p[3]), Distortion::None),
+        "SIMPLE_RADIAL" => (k(p[0], p[0], p[1], p[2]), radial(p[3], 0.0)),
+        "RADIAL" => (k(p[0], p[0], p[1], p[2]), radial(p[3], p[4])),
+        "OPENCV" => (
+            k(p[0], p[1], p[2], p[3]),
+            Distortion::BrownConrady {
+                k: [p[4], p[5], 0.0, 0.0, 0.0, 0.0],
+                p: [p[6], p[7]],
+            },
+        ),
+        "FULL_OPENCV" => (
+            k(p[0], p[1], p[2], p[3]),
+            Distortion::BrownConrady {
+                k: [p[4], p[5], p[8], p[9], p[10], p[11]],
+                p: [p[6], p[7]],
+            },
+        ),
+        "OPENCV_FISHEYE" => (
+            k(p[0], p[1], p[2], p[3]),
+            Distortion::Fisheye {
+                k: [p[4], p[5], p[6], p[7]],
+            },
+        ),
+        "SIMPLE_RADIAL_FISHEYE" => (
+            k(p[0], p[0], p[1], p[2]),
+            Distortion::Fisheye {
+                k: [p[3], 0.0, 0.0, 0.0],
+            },
+        ),
+        "RADIAL_FISHEYE" => (
+            k(p[0], p[0], p[1], p[2]),
+            Distortion::Fisheye {
+                k: [p[3], p[4], 0.0, 0.0],
+            },
+        ),
+        model => return Err(ReconstructionError::UnsupportedModel(model.into())),
+    })
+}
+
+fn assemble(
+    cameras: &HashMap<u32, Camera>,
+    images: Vec<ImageRecord>,
+    points: &HashMap<u64, Vec3A>,
+) -> Result<Vec<Image>, ReconstructionError> {
+    images
+        .into_iter()
+        .map(|image| {
+            let camera = cameras.get(&image.camera_id).ok_or_else(|| {
+                ReconstructionError::Parse(format!(
+                    "`{}` refers to the missing camera {}",
+                    image.name, image.camera_id
+                ))
+            })?;
+            let (k, distortion) = intrinsics(camera)?;
+
+            let correspondences = image
+                .points
+                .iter()
+                .filter_map(|&(keypoint, id)| {
+                    let world = *points.get(&id?)?;
+                    Some((
+                        distortion.undistort_pixel(&k, keypoint),
+                        WCoord::from(world),
+                    ))
+                })
+                .collect();
+            let ground_truth = Pose {
+                rotation: Mat3A::from_quat(image.rotation.normalize().as_f32()),
+                translation: image.translation.as_vec3().into(),
+            };
+
+            Ok(Image {
+                name: image.name,
+                distortion,
+                problem: Problem {
+                    k,
+                    correspondences,
+                    ground_truth: Some(ground_truth),
+                    inliers: None,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Little-endian reader of COLMAP's binary files
+struct Binary<R>(R);
+
+impl<R: Read> Binary<R> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], ReconstructionError> {
+        let mut bytes = [0; N];
+        self.0.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, ReconstructionError> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, ReconstructionError> {
+        self.bytes().map(u64::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> Result<f64, ReconstructionError> {
+        self.bytes().map(f64::from_le_bytes)
+    }
+
+    fn f64s<const N: usize>(&mut self) -> Result<[f64; N], ReconstructionError> {
+        let mut values = [0.0; N];
+        for value in &mut values {
+            *value = self.f64()?;
+        }
+
+        Ok(values)
+    }
+
+    /// Reads a null-terminated string
+    fn string(&mut self) -> Result<String, ReconstructionError> {
+        let mut bytes = vec![];
+        loop {
+            match self.bytes::<1>()? {
+                [0] => break,
+                [byte] => bytes.push(byte),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|e| ReconstructionError::Parse(e.to_string()))
+    }
+}
@@ -0,0 +1,415 @@
+use crate::corres::{Constraint, Corres, LineCorres, Threshold, VanishingCorres};
+use crate::linalg;
+use crate::Pose;
+use glam::{DMat3, DVec2, DVec3, Mat3, Mat3A, Vec3A};
+
+const MAX_ITERATIONS: usize = 50;
+const MAX_DAMPING: f64 = 1e10;
+/// Squared error charged for a point behind the camera
+const BEHIND_PENALTY: f64 = 1.0;
+
+/// Inlier world point, or direction, and what it projects onto, in normalized image coordinates
+struct Observation {
+    target: Target,
+    world: DVec3,
+    weight: f64,
+}
+
+enum Target {
+    Point(DVec2),
+    /// Line `l` of the points `p` with `l · (p, 1) = 0`, scaled to measure distances
+    Line(DVec3),
+    /// Unit ray of a vanishing point, which the rotated world direction is parallel to
+    Direction(DVec3),
+}
+
+impl Target {
+    /// Residuals at the camera coordinates `x` and their gradients in `x`; a line has a single
+    /// one, and a direction's third one is redundant
+    fn residuals(&self, x: DVec3) -> [(f64, DVec3); 3] {
+        let projection = || {
+            let p = x.truncate() / x.z;
+            // d(projection)/dx
+            let dp = [
+                DVec3::new(1.0 / x.z, 0.0, -x.x / (x.z * x.z)),
+                DVec3::new(0.0, 1.0 / x.z, -x.y / (x.z * x.z)),
+            ];
+            (p, dp)
+        };
+
+        match *self {
+            Target::Point(image) => {
+                let (p, dp) = projection();
+                [
+                    (p.x - image.x, dp[0]),
+                    (p.y - image.y, dp[1]),
+                    (0.0, DVec3::ZERO),
+                ]
+            }
+            Target::Line(line) => {
+                let (p, dp) = projection();
+                [
+                    (line.dot(p.extend(1.0)), dp[0] * line.x + dp[1] * line.y),
+                    (0.0, DVec3::ZERO),
+                    (0.0, DVec3::ZERO),
+                ]
+            }
+            // ray × x, whose gradient rows are those of [ray]x
+            Target::Direction(ray) => {
+                let r = ray.cross(x);
+                [
+                    (r.x, DVec3::new(0.0, -ray.z, ray.y)),
+                    (r.y, DVec3::new(ray.z, 0.0, -ray.x)),
+                    (r.z, DVec3::new(-ray.y, ray.x, 0.0)),
+                ]
+            }
+        }
+    }
+
+    fn dimension(&self) -> usize {
+        match self {
+            Target::Point(_) | Target::Direction(_) => 2,
+            Target::Line(_) => 1,
+        }
+    }
+
+    /// Camera coordinates of `world` under the pose; directions are not translated
+    fn camera(&self, rot: &DMat3, trans: DVec3, world: DVec3) -> DVec3 {
+        match self {
+            Target::Point(_) | Target::Line(_) => *rot * world + trans,
+            Target::Direction(_) => *rot * world,
+        }
+    }
+}
+
+/// Marks correspondences belonging to a pair which satisfies its constraint under `rot`, given
+/// the constraints of the pairs in order
+pub fn inliers(
+    corres: &[Corres],
+    constraints: &[Constraint],
+    rot: &Mat3A,
+    threshold: Threshold,
+) -> Vec<bool> {
+    let mut mask = vec![false; corres.len()];
+
+    for (i, satisfied) in satisfied(constraints, rot, threshold)
+        .into_iter()
+        .enumerate()
+    {
+        if satisfied {
+            mask[2 * i] = true;
+            mask[2 * i + 1] = true;
+        }
+    }
+
+    mask
+}
+
+/// Marks the constraints satisfied under `rot`
+pub fn satisfied(constraints: &[Constraint], rot: &Mat3A, threshold: Threshold) -> Vec<bool> {
+    constraints
+        .iter()
+        .map(|constraint| constraint.error(rot) < constraint.threshold(threshold))
+        .collect()
+}
+
+/// Estimates the translation given `rot`, the inlier pairs and the lines of inlier direction
+///
+/// Every inlier pair proposes the translation fitted to its two correspondences, and the lines
+/// together propose the one fitted to them; the one agreeing with most correspondences within
+/// `threshold` (angle between observed and predicted rays, or between the back-projected plane
+/// and the world line) is refitted on them. Returns the translation and the correspondences and
+/// lines agreeing with it.
+pub fn translation(
+    corres: &[Corres],
+    lines: &[LineCorres],
+    pair_inliers: &[bool],
+    direction_inliers: &[bool],
+    rot: &Mat3A,
+    threshold: Threshold,
+) -> (Vec3A, Vec<bool>, Vec<bool>) {
+    let agreeing = |translation: Vec3A| {
+        let pose = Pose {
+            rotation: *rot,
+            translation,
+        };
+
+        (
+            ray_inliers(corres, &pose, threshold),
+            line_inliers(lines, &pose, threshold),
+        )
+    };
+    let total_support = |(inliers, line_inliers): &(Vec<bool>, Vec<bool>)| {
+        support(corres, inliers) + line_support(lines, line_inliers)
+    };
+
+    let no_lines = vec![false; lines.len()];
+    let from_pairs = (0..corres.len() / 2)
+        .filter(|&i| pair_inliers[2 * i])
+        .filter_map(|i| fit_translation(&corres[2 * i..2 * i + 2], &[true, true], &[], &[], rot));
+    let no_points = vec![false; corres.len()];
+    let from_lines = fit_translation(corres, &no_points, lines, direction_inliers, rot);
+    let best = from_pairs
+        .chain(from_lines)
+        .map(agreeing)
+        .max_by(|a, b| total_support(a).total_cmp(&total_support(b)));
+
+    let Some(trans) = best.and_then(|(inliers, line_inliers)| {
+        fit_translation(corres, &inliers, lines, &line_inliers, rot)
+    }) else {
+        return (Vec3A::ZERO, no_points, no_lines);
+    };
+    let (inliers, line_inliers) = agreeing(trans);
+
+    (trans, inliers, line_inliers)
+}
+
+/// Marks correspondences whose ray is within `threshold` of the one predicted by `pose`
+pub fn ray_inliers(corres: &[Corres], pose: &Pose, threshold: Threshold) -> Vec<bool> {
+    corres
+        .iter()
+        .map(|c| ray_error(c, &pose.rotation, pose.translation) < c.threshold(threshold))
+        .collect()
+}
+
+/// Marks lines whose world points are in front of the camera and within `threshold` of the plane
+/// back-projected from the image line under `pose`
+pub fn line_inliers(lines: &[LineCorres], pose: &Pose, threshold: Threshold) -> Vec<bool> {
+    lines
+        .iter()
+        .map(|line| line.error(&pose.rotation, pose.translation) < line.threshold(threshold))
+        .collect()
+}
+
+/// Sums the weights of the inliers
+pub fn support(corres: &[Corres], inliers: &[bool]) -> f32 {
+    inlying(corres, inliers).map(Corres::weight).sum()
+}
+
+/// Sums the weights of the inlier lines
+pub fn line_support(lines: &[LineCorres], inliers: &[bool]) -> f32 {
+    inlying(lines, inliers).map(LineCorres::weight).sum()
+}
+
+/// Angle between the observed ray and `R * world + t`
+pub fn ray_error(c: &Corres, rot: &Mat3A, trans: Vec3A) -> f32 {
+    let predicted = *rot * c.world().as_vec3a() + trans;
+
+    c.projected().as_vec3a().angle_between(predicted)
+}
+
+/// Computes the translation minimizing the distances of `R * world + t` to the inliers' rays,
+/// and of the inlier lines' world points to their back-projected planes
+fn fit_translation(
+    corres: &[Corres],
+    inliers: &[bool],
+    lines: &[LineCorres],
+    line_inliers: &[bool],
+    rot: &Mat3A,
+) -> Option<Vec3A> {
+    let mut a = Mat3A::ZERO;
+    let mut b = Vec3A::ZERO;
+
+    for c in inlying(corres, inliers) {
+        let ray = c.projected().as_vec3a().normalize();
+        // projects onto the plane orthogonal to the ray
+        let p = Mat3A::IDENTITY - Mat3A::from_cols(ray * ray.x, ray * ray.y, ray * ray.z);
+
+        a += p;
+        b -= p * (*rot * c.world().as_vec3a());
+    }
+    for line in inlying(lines, line_inliers) {
+        let Some(normal) = line.normal().try_normalize() else {
+            continue;
+        };
+        // projects onto the normal of the plane
+        let p = Mat3A::from_cols(normal * normal.x, normal * normal.y, normal * normal.z);
+        for world in line.world() {
+            a += p;
+            b -= p * (*rot * world.as_vec3a());
+        }
+    }
+
+    if a.determinant().abs() < f32::EPSILON {
+        return None;
+    }
+
+    Some(a.inverse() * b)
+}
+
+/// Refines `pose` on the inliers by Levenberg-Marquardt on the weighted reprojection error
+///
+/// The rotation is updated on SO(3) as `exp(ω) * R`.
+pub fn refine(corres: &[Corres], inliers: &[bool], pose: Pose) -> Pose {
+    refine_mixed(corres, inliers, &[], &[], &[], &[], pose)
+}
+
+/// Refines as `refine`, adding the distances of the inlier lines' projected world points to the
+/// image lines, and the deviations of the inlier vanishing directions from their rays
+pub fn refine_mixed(
+    corres: &[Corres],
+    inliers: &[bool],
+    lines: &[LineCorres],
+    line_inliers: &[bool],
+    vanishing: &[VanishingCorres],
+    vanishing_inliers: &[bool],
+    pose: Pose,
+) -> Pose {
+    let points = inlying(corres, inliers).map(|c| {
+        let p = c.projected().as_vec3a().as_dvec3();
+        Observation {
+            target: Target::Point(p.truncate() / p.z),
+            world: c.world().as_vec3a().as_dvec3(),
+            weight: c.weight() as f64,
+        }
+    });
+    let lines = inlying(lines, line_inliers)
+        .filter_map(|line| {
+            let normal = line.normal().as_dvec3();
+            // the line at infinity has no distance
+            let scale = normal.truncate().length();
+            (scale > f64::EPSILON).then(|| {
+                line.world().map(|world| Observation {
+                    target: Target::Line(normal / scale),
+                    world: world.as_vec3a().as_dvec3(),
+                    weight: line.weight() as f64,
+                })
+            })
+        })
+        .flatten();
+    let directions = inlying(vanishing, vanishing_inliers).filter_map(|vanishing| {
+        let ray = vanishing
+            .projected()
+            .as_vec3a()
+            .as_dvec3()
+            .try_normalize()?;
+        let direction = vanishing.direction().as_dvec3().try_normalize()?;
+        Some(Observation {
+            target: Target::Direction(ray),
+            world: direction,
+            weight: vanishing.weight() as f64,
+        })
+    });
+    let observations: Vec<Observation> = points.chain(lines).chain(directions).collect();
+    // as many residuals as the 6 degrees of freedom
+    if observations
+        .iter()
+        .map(|o| o.target.dimension())
+        .sum::<usize>()
+        < 6
+    {
+        return pose;
+    }
+
+    let mut rot = Mat3::from(pose.rotation).as_dmat3();
+    let mut trans = pose.translation.as_dvec3();
+    let mut cost = reprojection_cost(&observations, &rot, trans);
+    let mut damping = 1e-3;
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut jtj = [[0.0; 6]; 6];
+        let mut jtr = [0.0; 6];
+
+        for Observation {
+            target,
+            world,
+            weight,
+        } in &observations
+        {
+            let rw = rot * *world;
+            let x = target.camera(&rot, trans, *world);
+            let translated = !matches!(target, Target::Direction(_));
+            if translated && x.z <= f64::EPSILON {
+                continue;
+            }
+
+            // dx/d(ω, t) = [ -[Rw]x | I ], without the translation for a direction
+            let t = if translated { 1.0 } else { 0.0 };
+            let dx = [
+                [0.0, rw.z, -rw.y, t, 0.0, 0.0],
+                [-rw.z, 0.0, rw.x, 0.0, t, 0.0],
+                [rw.y, -rw.x, 0.0, 0.0, 0.0, t],
+            ];
+
+            for (r, gradient) in target.residuals(x) {
+                let dr = gradient.to_array();
+                let j: [f64; 6] = std::array::from_fn(|k| (0..3).map(|m| dr[m] * dx[m][k]).sum());
+                for row in 0..6 {
+                    jtr[row] += weight * j[row] * r;
+                    for col in 0..6 {
+                        jtj[row][col] += weight * j[row] * j[col];
+                    }
+                }
+            }
+        }
+
+        loop {
+            let mut a = jtj;
+            for (i, row) in a.iter_mut().enumerate() {
+                row[i] += damping * jtj[i][i].max(f64::EPSILON);
+            }
+            let Some(delta) = linalg::solve(a, jtr.map(|v| -v)) else {
+                return to_pose(&rot, trans);
+            };
+
+            let omega = DVec3::new(delta[0], delta[1], delta[2]);
+            let next_rot = match omega.try_normalize() {
+                Some(axis) => DMat3::from_axis_angle(axis, omega.length()) * rot,
+                None => rot,
+            };
+            let next_trans = trans + DVec3::new(delta[3], delta[4], delta[5]);
+            let next_cost = reprojection_cost(&observations, &next_rot, next_trans);
+
+            if next_cost < cost {
+                let converged = (cost - next_cost) < 1e-12 * cost.max(f64::EPSILON);
+                rot = next_rot;
+                trans = next_trans;
+                cost = next_cost;
+                damping = (damping / 10.0).max(1e-12);
+                if converged {
+                    return to_pose(&rot, trans);
+                }
+                break;
+            }
+
+            damping *= 10.0;
+            if damping > MAX_DAMPING {
+                return to_pose(&rot, trans);
+            }
+        }
+    }
+
+    to_pose(&rot, trans)
+}
+
+fn inlying<'a, T>(items: &'a [T], inliers: &'a [bool]) -> impl Iterator<Item = &'a T> {
+    items
+        .iter()
+        .zip(inliers)
+        .filter_map(|(item, &inlier)| inlier.then_some(item))
+}
+
+fn reprojection_cost(observations: &[Observation], rot: &DMat3, trans: DVec3) -> f64 {
+    observations
+        .iter()
+        .map(|o| {
+            let x = o.target.camera(rot, trans, o.world);
+            if !matches!(o.target, Target::Direction(_)) && x.z <= f64::EPSILON {
+                return o.weight * BEHIND_PENALTY;
+            }
+            o.weight
+                * o.target
+                    .residuals(x)
+                    .iter()
+                    .map(|(r, _)| r * r)
+                    .sum::<f64>()
+        })
+        .sum()
+}
+
+fn to_pose(rot: &DMat3, trans: DVec3) -> Pose {
+    Pose {
+        rotation: rot.as_mat3().into(),
+        translation: trans.as_vec3().into(),
+    }
+}
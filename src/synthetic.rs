@@ -0,0 +1,245 @@
+//! Seeded generator of synthetic problems with known poses and inlier labels
+
+use crate::problem::Problem;
+use crate::types::WCoord;
+use crate::{CameraK, Pose};
+use glam::{IVec2, Mat3A, Vec3A};
+use rand::rngs::StdRng;
+use rand::seq::index;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Normal, UnitSphere};
+
+/// Attempts per requested point before giving up on an invisible cloud
+const MAX_ATTEMPTS_PER_POINT: usize = 1000;
+
+/// Distribution of the world points
+#[derive(Clone, Copy, Debug)]
+pub enum Cloud {
+    /// Uniform in the box between `min` and `max`
+    Uniform { min: Vec3A, max: Vec3A },
+    /// Uniform in the square of side `2 * half_size` about `center`, orthogonal to `normal`
+    Planar {
+        center: Vec3A,
+        normal: Vec3A,
+        half_size: f32,
+    },
+    /// Uniform in the cube of side `size` about `center`
+    ///
+    /// A small cube far from the camera is seen under a narrow angle, the quasi-singular case of
+    /// PnP.
+    QuasiSingular { center: Vec3A, size: f32 },
+    /// Gaussian clusters of standard deviation `spread` about `clusters` centers uniform in the
+    /// box between `min` and `max`
+    Clustered {
+        min: Vec3A,
+        max: Vec3A,
+        clusters: usize,
+        spread: f32,
+    },
+}
+
+impl Cloud {
+    /// One cloud of each kind about the origin, by name, as the scenes of the examples and
+    /// benchmarks
+    pub fn presets() -> [(&'static str, Cloud); 4] {
+        [
+            (
+                "uniform",
+                Cloud::Uniform {
+                    min: Vec3A::splat(-2.0),
+                    max: Vec3A::splat(2.0),
+                },
+            ),
+            (
+                "planar",
+                Cloud::Planar {
+                    center: Vec3A::ZERO,
+                    normal: Vec3A::new(0.2, 0.3, 1.0),
+                    half_size: 3.0,
+                },
+            ),
+            (
+                "quasi-singular",
+                Cloud::QuasiSingular {
+                    center: Vec3A::ZERO,
+                    size: 0.5,
+                },
+            ),
+            (
+                "clustered",
+                Cloud::Clustered {
+                    min: Vec3A::splat(-2.0),
+                    max: Vec3A::splat(2.0),
+                    clusters: 4,
+                    spread: 0.3,
+                },
+            ),
+        ]
+    }
+}
+
+/// Camera of `pose` looking at `target` from `eye`, with `up` pointing up in the image
+pub fn look_at(eye: Vec3A, target: Vec3A, up: Vec3A) -> Pose {
+    // image y points down
+    let z = (target - eye).normalize();
+    let x = (-up).cross(z).normalize();
+    let y = z.cross(x);
+    let rotation = Mat3A::from_cols(x, y, z).transpose();
+
+    Pose {
+        rotation,
+        translation: -(rotation * eye),
+    }
+}
+
+/// Seeded generator of cameras, point clouds and noisy correspondences
+pub struct Generator {
+    rng: StdRng,
+    pub k: CameraK,
+    /// Width and height of the image in pixels
+    pub image_size: IVec2,
+    /// Standard deviation of the pixel noise of the inliers
+    pub noise: f32,
+    /// Fraction of the correspondences whose image point is replaced by a random pixel
+    pub outlier_ratio: f32,
+}
+
+impl Generator {
+    /// Generator of noise-free, outlier-free problems of a KITTI-like camera
+    pub fn new(seed: u64) -> Self {
+        Generator {
+            rng: StdRng::seed_from_u64(seed),
+            k: CameraK {
+                fx: 718.856,
+                fy: 718.856,
+                cx: 607.1928,
+                cy: 185.2157,
+            },
+            image_size: IVec2::new(1241, 376),
+            noise: 0.0,
+            outlier_ratio: 0.0,
+        }
+    }
+
+    /// Camera at `distance` from `target` in a random direction, looking at it
+    pub fn random_pose(&mut self, target: Vec3A, distance: f32) -> Pose {
+        let direction = Vec3A::from(UnitSphere.sample(&mut self.rng));
+        let up = if direction.z.abs() < 0.9 {
+            Vec3A::Z
+        } else {
+            Vec3A::Y
+        };
+
+        look_at(target + direction * distance, target, up)
+    }
+
+    /// Samples `n` world points of `cloud`
+    pub fn points(&mut self, cloud: &Cloud, n: usize) -> Vec<Vec3A> {
+        match *cloud {
+            Cloud::Uniform { min, max } => (0..n).map(|_| self.uniform(min, max)).collect(),
+            Cloud::Planar {
+                center,
+                normal,
+                half_size,
+            } => {
+                let normal = normal.normalize();
+                let u = normal.any_orthonormal_vector();
+                let v = normal.cross(u);
+                (0..n)
+                    .map(|_| {
+                        let a = self.rng.gen_range(-half_size..=half_size);
+                        let b = self.rng.gen_range(-half_size..=half_size);
+                        center + u * a + v * b
+                    })
+                    .collect()
+            }
+            Cloud::QuasiSingular { center, size } => {
+                let half = Vec3A::splat(size / 2.0);
+                (0..n)
+                    .map(|_| self.uniform(center - half, center + half))
+                    .collect()
+            }
+            Cloud::Clustered {
+                min,
+                max,
+                clusters,
+                spread,
+            } => {
+                let centers: Vec<Vec3A> = (0..clusters.max(1))
+                    .map(|_| self.uniform(min, max))
+                    .collect();
+                let normal = Normal::new(0.0, spread).expect("spread must be non-negative");
+                (0..n)
+                    .map(|_| {
+                        let center = centers[self.rng.gen_range(0..centers.len())];
+                        let offset: [f32; 3] =
+                            std::array::from_fn(|_| normal.sample(&mut self.rng));
+                        center + Vec3A::from(offset)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Generates `n` correspondences of the points of `cloud` visible from `pose`
+    ///
+    /// The outliers are placed at random positions; the result has fewer correspondences when
+    /// too few points of `cloud` are visible.
+    pub fn problem(&mut self, pose: &Pose, cloud: &Cloud, n: usize) -> Problem {
+        let noise = Normal::new(0.0, self.noise).expect("noise must be non-negative");
+        let mut correspondences = Vec::with_capacity(n);
+
+        for _ in 0..n * MAX_ATTEMPTS_PER_POINT {
+            if correspondences.len() == n {
+                break;
+            }
+            let [world] = self.points(cloud, 1)[..] else {
+                continue;
+            };
+
+            // tests the visibility in camera coordinates
+            let camera = pose.rotation * world + pose.translation;
+            if camera.z <= 0.0 {
+                continue;
+            }
+            let image = self.k.as_mat3a() * (camera / camera.z);
+            let image = image.truncate()
+                + glam::Vec2::new(noise.sample(&mut self.rng), noise.sample(&mut self.rng));
+            let image = image.round().as_ivec2();
+            if self.contains(image) {
+                correspondences.push((image.into(), WCoord::from(world)));
+            }
+        }
+
+        let n = correspondences.len();
+        let n_outliers = ((n as f32) * self.outlier_ratio.clamp(0.0, 1.0)).round() as usize;
+        let mut inliers = vec![true; n];
+        for i in index::sample(&mut self.rng, n, n_outliers) {
+            let pixel = IVec2::new(
+                self.rng.gen_range(0..self.image_size.x),
+                self.rng.gen_range(0..self.image_size.y),
+            );
+            correspondences[i].0 = pixel.into();
+            inliers[i] = false;
+        }
+
+        Problem {
+            k: self.k,
+            correspondences,
+            ground_truth: Some(*pose),
+            inliers: Some(inliers),
+        }
+    }
+
+    fn uniform(&mut self, min: Vec3A, max: Vec3A) -> Vec3A {
+        Vec3A::new(
+            self.rng.gen_range(min.x..=max.x),
+            self.rng.gen_range(min.y..=max.y),
+            self.rng.gen_range(min.z..=max.z),
+        )
+    }
+
+    fn contains(&self, image: IVec2) -> bool {
+        image.cmpge(IVec2::ZERO).all() && image.cmplt(self.image_size).all()
+    }
+}
@@ -0,0 +1,245 @@
+//! Recording of the branch-and-bound search, to inspect how tight the bounds are
+
+use glam::Vec3A;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::time::Instant;
+
+/// Parametrization ranges of a branch of the rotation search
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cell {
+    /// Cube of angle-axis vectors
+    AngleAxis { center: Vec3A, edge: f32 },
+    /// Axis polar angle `theta`, azimuth `phi` and rotation `angle`
+    Polar {
+        theta: [f32; 2],
+        phi: [f32; 2],
+        angle: [f32; 2],
+    },
+    /// Rotation angle about a fixed axis
+    Axis { angle: [f32; 2] },
+}
+
+impl Cell {
+    /// `[min, max]` of each parameter
+    pub fn ranges(&self) -> Vec<[f32; 2]> {
+        match *self {
+            Cell::AngleAxis { center, edge } => center
+                .to_array()
+                .map(|c| [c - edge / 2.0, c + edge / 2.0])
+                .to_vec(),
+            Cell::Polar { theta, phi, angle } => vec![theta, phi, angle],
+            Cell::Axis { angle } => vec![angle],
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Cell::AngleAxis { .. } => "angle-axis",
+            Cell::Polar { .. } => "polar",
+            Cell::Axis { .. } => "axis",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    /// The branch was subdivided
+    Expanded,
+    /// The branch was discarded as its upper bound is below the best score
+    Pruned,
+}
+
+impl EventKind {
+    fn name(&self) -> &'static str {
+        match self {
+            EventKind::Expanded => "expanded",
+            EventKind::Pruned => "pruned",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Event {
+    pub kind: EventKind,
+    pub cell: Cell,
+    /// Subdivisions from the initial branch
+    pub depth: u32,
+    pub upper: f32,
+    pub lower: f32,
+    /// Seconds since the trace was created
+    pub time: f32,
+}
+
+/// Expanded and pruned branches of the searches run by `Solver::solve_traced`
+#[derive(Clone, Debug)]
+pub struct Trace {
+    start: Instant,
+    events: Vec<Event>,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Trace::new()
+    }
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Trace {
+            start: Instant::now(),
+            events: vec![],
+        }
+    }
+
+    /// Events in the order they happened
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        kind: EventKind,
+        cell: Cell,
+        depth: u32,
+        upper: f32,
+        lower: f32,
+    ) {
+        self.events.push(Event {
+            kind,
+            cell,
+            depth,
+            upper,
+            lower,
+            time: self.start.elapsed().as_secs_f32(),
+        });
+    }
+
+    /// Writes one JSON object per event
+    pub fn write_jsonl(&self, mut writer: impl Write) -> io::Result<()> {
+        for event in &self.events {
+            let ranges: Vec<String> = event
+                .cell
+                .ranges()
+                .iter()
+                .map(|[min, max]| format!("[{min}, {max}]"))
+                .collect();
+            writeln!(
+                writer,
+                "{{\"event\": \"{}\", \"cell\": \"{}\", \"ranges\": [{}], \"depth\": {}, \"upper\": {}, \"lower\": {}, \"time\": {}}}",
+                event.kind.name(),
+                event.cell.name(),
+                ranges.join(", "),
+                event.depth,
+                event.upper,
+                event.lower,
+                event.time
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes one CSV row per event, with the ranges of missing parameters left empty
+    pub fn write_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(
+            writer,
+            "event,cell,depth,upper,lower,time,min0,max0,min1,max1,min2,max2"
+        )?;
+        for event in &self.events {
+            let mut ranges = event.cell.ranges();
+            ranges.resize(3, [f32::NAN; 2]);
+            let ranges: Vec<String> = ranges
+                .iter()
+                .flatten()
+                .map(|value| {
+                    if value.is_nan() {
+                        String::new()
+                    } else {
+                        value.to_string()
+                    }
+                })
+                .collect();
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                event.kind.name(),
+                event.cell.name(),
+                event.depth,
+                event.upper,
+                event.lower,
+                event.time,
+                ranges.join(",")
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Renders the cells projected onto the parameters `dims`, e.g. `[0, 1]` for the x and y of
+    /// angle-axis cells or the theta and phi of polar cells
+    ///
+    /// Expanded cells are filled in proportion to their upper bound, and pruned cells are
+    /// outlined in red. Cells lacking either parameter are skipped.
+    pub fn render_svg(&self, dims: [usize; 2]) -> String {
+        const SIZE: f32 = 800.0;
+        const MARGIN: f32 = 10.0;
+
+        let mut cells: Vec<(&Event, [f32; 2], [f32; 2])> = self
+            .events
+            .iter()
+            .filter_map(|event| {
+                let ranges = event.cell.ranges();
+                Some((event, *ranges.get(dims[0])?, *ranges.get(dims[1])?))
+            })
+            .collect();
+
+        let (mut min, mut max) = ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]);
+        for (_, x, y) in &cells {
+            for (i, range) in [x, y].into_iter().enumerate() {
+                min[i] = min[i].min(range[0]);
+                max[i] = max[i].max(range[1]);
+            }
+        }
+        let scale = [0, 1].map(|i| (SIZE - 2.0 * MARGIN) / (max[i] - min[i]).max(f32::EPSILON));
+        let max_upper = cells
+            .iter()
+            .map(|(event, _, _)| event.upper)
+            .fold(0.0, f32::max)
+            .max(f32::EPSILON);
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{SIZE}\" height=\"{SIZE}\" viewBox=\"0 0 {SIZE} {SIZE}\">\n"
+        );
+        let _ = writeln!(
+            svg,
+            "<rect width=\"{SIZE}\" height=\"{SIZE}\" fill=\"white\"/>"
+        );
+
+        // coarse cells first, so the finer ones are drawn over them
+        cells.sort_by_key(|(event, _, _)| event.depth);
+        for (event, x, y) in cells {
+            let left = MARGIN + (x[0] - min[0]) * scale[0];
+            let width = (x[1] - x[0]) * scale[0];
+            // parameters grow upwards
+            let top = SIZE - MARGIN - (y[1] - min[1]) * scale[1];
+            let height = (y[1] - y[0]) * scale[1];
+            let style = match event.kind {
+                EventKind::Expanded => format!(
+                    "fill=\"steelblue\" fill-opacity=\"{}\" stroke=\"navy\" stroke-width=\"0.5\"",
+                    0.1 + 0.9 * (event.upper / max_upper).clamp(0.0, 1.0)
+                ),
+                EventKind::Pruned => {
+                    "fill=\"none\" stroke=\"red\" stroke-width=\"0.5\"".to_string()
+                }
+            };
+            let _ = writeln!(
+                svg,
+                "<rect x=\"{left}\" y=\"{top}\" width=\"{width}\" height=\"{height}\" {style}/>"
+            );
+        }
+        svg.push_str("</svg>\n");
+
+        svg
+    }
+}
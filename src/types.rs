@@ -3,14 +3,17 @@ use std::ops::Sub;
 
 /// Represents world coordinate
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WCoord(Vec3A);
 
 /// Represents camera coordinate
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CCoord(Vec3A);
 
 /// Represents image plane coordinate
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ICoord(IVec2);
 
 impl WCoord {